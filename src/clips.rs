@@ -17,8 +17,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::text::process_string;
 
-use super::eaf::{select_tier, select_annotation};
-use super::files::confirm;
+use super::eaf::{can_prompt, resolve_annotation, resolve_tier};
+use super::files::{checked_outpath, confirm};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct Clips {
@@ -156,6 +156,9 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     let ffmpeg = args.get_one::<String>("ffmpeg").unwrap(); // clap default ffmpeg/ffmpeg.exe
     // let extract_wav = args.is_present("extract-wav"); // clap default ffmpeg/ffmpeg.exe
     let ascii_path = *args.get_one::<bool>("ascii-path").unwrap();
+    let tier_selector = args.get_one::<String>("tier").map(|s| s.as_str());
+    let annotation_selector = args.get_one::<String>("annotation").map(|s| s.as_str());
+    let interactive = *args.get_one::<bool>("interactive").unwrap();
 
     let eaf = match Eaf::read(eaf_path) {
         Ok(f) => f,
@@ -204,7 +207,7 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     let tiers = if extract_all {
         eaf.tiers
     } else {
-        match select_tier(&eaf, true) {
+        match resolve_tier(&eaf, tier_selector, interactive, true) {
             Ok(t) => vec![t],
             Err(err) => {
                 let msg = format!("Failed to extract tier: {err}");
@@ -239,13 +242,13 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                 let len = tier.len();
 
                 // let user choose whether to list large tiers
-                if len > 40 {
+                if len > 40 && annotation_selector.is_none() && can_prompt(interactive) {
                     if !confirm(&format!("The tier '{}' contains {} annotations. List all?", tier.tier_id, len))? {
                         return Err(std::io::Error::new(std::io::ErrorKind::Other, "User aborted process."))
                     }
                 }
 
-                let annotation = match select_annotation(&tier) {
+                let annotation = match resolve_annotation(&tier, annotation_selector, interactive) {
                     Ok(a) => a,
                     Err(err) => {
                         let msg = format!("Failed to extract annotation: {err}");
@@ -349,23 +352,24 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                     // via `with_extension` since this will yield "audio.wav_ADDED_SUFFIX".with_extention("wav") -> "audio.wav"
                     let outpath = tier_outdir.join(Path::new(&format!("{mediastem}_{annotstem}.{}", ext.to_string_lossy())));
                         // .with_extension(ext));
-                    clip.add(&outpath);
 
                     if dryrun {
+                        clip.add(&outpath);
                         println!("  IN (exists {:5}): {}\n OUT (exists {:5}): {}", media_in.exists(), media_in.display(), outpath.exists(), outpath.display());
                     } else {
-                        if outpath.exists() {
-                            match confirm(&format!("'{}' already exists. Overwrite?", outpath.display())) {
-                                Ok(false) => {
-                                    return Err(std::io::Error::new(std::io::ErrorKind::Other, "User aborted process."))
-                                },
-                                Ok(true) => (),
-                                Err(err) => {
-                                    let msg = format!("Failed to read input: {err}.");
-                                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
-                                }
+                        // Existing clips are kept as is unless overwritten or renamed
+                        let outpath = match checked_outpath(&outpath) {
+                            Ok(Some(p)) => p,
+                            Ok(None) => {
+                                println!("Skipped '{}'", outpath.display());
+                                clip.add(&outpath);
+                                continue
+                            },
+                            Err(err) => {
+                                let msg = format!("Failed to read input: {err}.");
+                                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
                             }
-                        }
+                        };
                         let media_out = match FFmpeg::extract_timespan(
                             &media_in,
                             *start_ms as u64,
@@ -379,6 +383,7 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                             }
                         };
         
+                        clip.add(&media_out);
                         println!("Wrote '{}'", media_out.display());
                     }
 
@@ -0,0 +1,509 @@
+//! Inter-annotator agreement between two tiers, with the first tier
+//! regarded as reference where it matters (boundary precision/recall).
+//!
+//! - Cohen's kappa and Krippendorff's alpha (nominal) on time-sliced categories.
+//!   Each tier is sampled at fixed intervals, and the annotation value at each
+//!   sample is used as category. Samples where neither tier has an annotation
+//!   are ignored, samples where only one tier has an annotation use the
+//!   category `<none>` for the other tier.
+//! - Segmentation overlap, the annotated time both tiers share,
+//!   relative to the annotated time in either tier.
+//! - Boundary precision, recall and F-score, where a boundary (annotation
+//!   start or end) matches if it is within the tolerance window of an unmatched
+//!   boundary in the other tier.
+//! - Label confusion matrix, in number of time slices.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use eaf_rs::eaf::{Eaf, Tier};
+
+use crate::eaf::annotation_timespan;
+
+/// Category for time slices without an annotation.
+pub const NONE_LABEL: &str = "<none>";
+
+/// Label confusion matrix in number of time slices.
+/// Key: `(label tier 1, label tier 2)`.
+pub type Confusion = BTreeMap<(String, String), usize>;
+
+/// Time-aligned annotation value.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub start: i64,
+    pub end: i64,
+    pub label: String,
+}
+
+/// Returns annotations in `tier` as segments, sorted on start time.
+/// Referred annotations use the time span of their main annotation.
+/// Annotations without time values are ignored.
+pub fn segments(eaf: &Eaf, tier: &Tier) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = tier.annotations.iter()
+        .filter_map(|a| match annotation_timespan(eaf, &tier.tier_id, &a.id()) {
+            (Some(start), Some(end)) => Some(Segment {
+                start,
+                end,
+                label: a.to_str().trim().to_owned(),
+            }),
+            _ => None
+        })
+        .collect();
+    segments.sort_by_key(|s| (s.start, s.end));
+    segments
+}
+
+/// Cursor over segments sorted on start time,
+/// for label lookups at increasing times.
+struct LabelCursor<'a> {
+    segments: &'a [Segment],
+    /// Index of the next segment not yet started.
+    next: usize,
+    /// Indices of started segments that may still cover later times, in order.
+    active: Vec<usize>,
+}
+
+impl<'a> LabelCursor<'a> {
+    fn new(segments: &'a [Segment]) -> Self {
+        Self {
+            segments,
+            next: 0,
+            active: Vec::new(),
+        }
+    }
+
+    /// Returns the label of the first segment covering `time`.
+    /// `time` must not be less than in the previous call.
+    fn label_at(&mut self, time: i64) -> Option<&'a str> {
+        while self.segments.get(self.next).map(|s| s.start <= time).unwrap_or(false) {
+            self.active.push(self.next);
+            self.next += 1;
+        }
+        let segments = self.segments;
+        self.active.retain(|i| time < segments[*i].end);
+        self.active.first().map(|i| segments[*i].label.as_str())
+    }
+}
+
+/// Agreement between two tiers.
+#[derive(Debug, Clone, Default)]
+pub struct Agreement {
+    /// Source of the tiers, e.g. file name, if set.
+    pub source: Option<String>,
+    pub tier1: String,
+    pub tier2: String,
+    /// Time slice length in milliseconds.
+    pub slice_ms: i64,
+    /// Boundary tolerance in milliseconds.
+    pub tolerance_ms: i64,
+    /// Number of time slices where at least one tier has an annotation.
+    pub slices: usize,
+    /// Observed agreement on time slices (0.0 - 1.0).
+    pub observed: f64,
+    pub kappa: Option<f64>,
+    pub alpha: Option<f64>,
+    /// Annotated time in both tiers, milliseconds.
+    pub overlap_ms: i64,
+    /// Annotated time in either tier, milliseconds.
+    pub union_ms: i64,
+    pub boundaries1: usize,
+    pub boundaries2: usize,
+    pub boundaries_matched: usize,
+    pub confusion: Confusion,
+}
+
+impl Agreement {
+    /// Computes agreement between `segments1` (reference) and `segments2`,
+    /// both sorted on start time, see `segments`.
+    pub fn new(
+        tier1: &str,
+        tier2: &str,
+        segments1: &[Segment],
+        segments2: &[Segment],
+        slice_ms: i64,
+        tolerance_ms: i64,
+    ) -> Self {
+        let mut confusion = Confusion::new();
+        for (l1, l2) in slice_pairs(segments1, segments2, slice_ms).into_iter() {
+            *confusion.entry((l1, l2)).or_insert(0) += 1;
+        }
+
+        let (overlap_ms, union_ms) = overlap(segments1, segments2);
+
+        let b1 = boundaries(segments1);
+        let b2 = boundaries(segments2);
+        let boundaries_matched = match_boundaries(&b1, &b2, tolerance_ms);
+
+        Self {
+            source: None,
+            tier1: tier1.to_owned(),
+            tier2: tier2.to_owned(),
+            slice_ms,
+            tolerance_ms,
+            overlap_ms,
+            union_ms,
+            boundaries1: b1.len(),
+            boundaries2: b2.len(),
+            boundaries_matched,
+            ..Self::default()
+        }.with_confusion(confusion)
+    }
+
+    /// Pools `agreements`, e.g. for the same tier pair across files,
+    /// by summing confusion matrices, time spans and boundaries,
+    /// then re-calculating kappa and alpha.
+    pub fn pooled(source: &str, tier1: &str, tier2: &str, agreements: &[Self]) -> Self {
+        let mut confusion = Confusion::new();
+        for ((l1, l2), count) in agreements.iter().flat_map(|a| a.confusion.iter()) {
+            *confusion.entry((l1.to_owned(), l2.to_owned())).or_insert(0) += count;
+        }
+
+        Self {
+            source: Some(source.to_owned()),
+            tier1: tier1.to_owned(),
+            tier2: tier2.to_owned(),
+            slice_ms: agreements.first().map(|a| a.slice_ms).unwrap_or_default(),
+            tolerance_ms: agreements.first().map(|a| a.tolerance_ms).unwrap_or_default(),
+            overlap_ms: agreements.iter().map(|a| a.overlap_ms).sum(),
+            union_ms: agreements.iter().map(|a| a.union_ms).sum(),
+            boundaries1: agreements.iter().map(|a| a.boundaries1).sum(),
+            boundaries2: agreements.iter().map(|a| a.boundaries2).sum(),
+            boundaries_matched: agreements.iter().map(|a| a.boundaries_matched).sum(),
+            ..Self::default()
+        }.with_confusion(confusion)
+    }
+
+    /// Sets confusion matrix, and derives the time slice based metrics.
+    fn with_confusion(self, confusion: Confusion) -> Self {
+        let slices: usize = confusion.values().sum();
+        let agreeing: usize = confusion.iter()
+            .filter(|((l1, l2), _)| l1 == l2)
+            .map(|(_, c)| c)
+            .sum();
+        Self {
+            slices,
+            observed: match slices {
+                0 => 0.,
+                n => agreeing as f64 / n as f64
+            },
+            kappa: cohens_kappa(&confusion),
+            alpha: krippendorffs_alpha(&confusion),
+            confusion,
+            ..self
+        }
+    }
+
+    /// Sets source, e.g. file name.
+    pub fn with_source(self, source: &str) -> Self {
+        Self {
+            source: Some(source.to_owned()),
+            ..self
+        }
+    }
+
+    /// Annotated time in both tiers, relative to annotated time in either tier.
+    pub fn overlap_ratio(&self) -> Option<f64> {
+        match self.union_ms {
+            0 => None,
+            u => Some(self.overlap_ms as f64 / u as f64)
+        }
+    }
+
+    /// Share of boundaries in tier 2 that match a boundary in tier 1.
+    pub fn precision(&self) -> Option<f64> {
+        match self.boundaries2 {
+            0 => None,
+            n => Some(self.boundaries_matched as f64 / n as f64)
+        }
+    }
+
+    /// Share of boundaries in tier 1 that match a boundary in tier 2.
+    pub fn recall(&self) -> Option<f64> {
+        match self.boundaries1 {
+            0 => None,
+            n => Some(self.boundaries_matched as f64 / n as f64)
+        }
+    }
+
+    pub fn f_score(&self) -> Option<f64> {
+        match (self.precision(), self.recall()) {
+            (Some(p), Some(r)) if p + r > 0. => Some(2. * p * r / (p + r)),
+            (Some(_), Some(_)) => Some(0.),
+            _ => None
+        }
+    }
+
+    /// All labels in either tier, sorted, with `<none>` last.
+    pub fn labels(&self) -> Vec<String> {
+        let labels: BTreeSet<&String> = self.confusion.keys()
+            .flat_map(|(l1, l2)| [l1, l2])
+            .collect();
+        let (mut labels, none): (Vec<String>, Vec<String>) = labels.into_iter()
+            .cloned()
+            .partition(|l| l != NONE_LABEL);
+        labels.extend(none);
+        labels
+    }
+
+    /// Metric names and values, in the order used for reports and CSV.
+    pub fn metrics(&self) -> Vec<(&'static str, String)> {
+        let opt = |v: Option<f64>| v.map(|n| format!("{n:.4}")).unwrap_or("NA".to_owned());
+        vec![
+            ("source", self.source.to_owned().unwrap_or_default()),
+            ("tier1", self.tier1.to_owned()),
+            ("tier2", self.tier2.to_owned()),
+            ("slice_ms", self.slice_ms.to_string()),
+            ("slices", self.slices.to_string()),
+            ("observed_agreement", format!("{:.4}", self.observed)),
+            ("cohens_kappa", opt(self.kappa)),
+            ("krippendorffs_alpha", opt(self.alpha)),
+            ("overlap_ms", self.overlap_ms.to_string()),
+            ("union_ms", self.union_ms.to_string()),
+            ("overlap_ratio", opt(self.overlap_ratio())),
+            ("tolerance_ms", self.tolerance_ms.to_string()),
+            ("boundaries1", self.boundaries1.to_string()),
+            ("boundaries2", self.boundaries2.to_string()),
+            ("boundaries_matched", self.boundaries_matched.to_string()),
+            ("boundary_precision", opt(self.precision())),
+            ("boundary_recall", opt(self.recall())),
+            ("boundary_f_score", opt(self.f_score())),
+        ]
+    }
+
+    /// Prints agreement report, including confusion matrix.
+    pub fn print(&self) {
+        let opt = |v: Option<f64>| v.map(|n| format!("{n:.4}")).unwrap_or("NA".to_owned());
+        match &self.source {
+            Some(source) => println!("[ Agreement ] '{}' (reference) vs '{}' ({source})", self.tier1, self.tier2),
+            None => println!("[ Agreement ] '{}' (reference) vs '{}'", self.tier1, self.tier2)
+        }
+        println!("  Time slices          | {} x {} ms", self.slices, self.slice_ms);
+        println!("  Observed agreement   | {:.4}", self.observed);
+        println!("  Cohen's kappa        | {}", opt(self.kappa));
+        println!("  Krippendorff's alpha | {}", opt(self.alpha));
+        println!("[ Segmentation ]");
+        println!("  Overlap              | {} of {} ms ({})", self.overlap_ms, self.union_ms, opt(self.overlap_ratio()));
+        println!("  Boundaries           | {} / {}, {} matched within +/- {} ms",
+            self.boundaries1, self.boundaries2, self.boundaries_matched, self.tolerance_ms);
+        println!("  Precision            | {}", opt(self.precision()));
+        println!("  Recall               | {}", opt(self.recall()));
+        println!("  F-score              | {}", opt(self.f_score()));
+
+        let labels = self.labels();
+        if labels.is_empty() {
+            return
+        }
+        let width = labels.iter()
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0)
+            .clamp(6, 20);
+        println!("[ Confusion matrix ] rows: '{}', columns: '{}', values: time slices", self.tier1, self.tier2);
+        print!("  {:width$}", "");
+        for label in labels.iter() {
+            print!(" {:>width$}", truncate(label, width));
+        }
+        println!();
+        for l1 in labels.iter() {
+            print!("  {:width$}", truncate(l1, width));
+            for l2 in labels.iter() {
+                let count = self.confusion.get(&(l1.to_owned(), l2.to_owned())).unwrap_or(&0);
+                print!(" {:>width$}", count);
+            }
+            println!();
+        }
+    }
+
+    /// Returns metrics as CSV, with headers.
+    pub fn metrics_csv(agreements: &[Self], delimiter: u8) -> std::io::Result<String> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(vec![]);
+        if let Some(first) = agreements.first() {
+            writer.write_record(first.metrics().iter().map(|(k, _)| k))?;
+        }
+        for agreement in agreements.iter() {
+            writer.write_record(agreement.metrics().iter().map(|(_, v)| v))?;
+        }
+        let bytes = writer.into_inner().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        String::from_utf8(bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    }
+
+    /// Returns confusion matrix as CSV, with labels in tier 2 as headers,
+    /// and labels in tier 1 in the first column.
+    pub fn confusion_csv(&self, delimiter: u8) -> std::io::Result<String> {
+        let labels = self.labels();
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(vec![]);
+        let mut headers = vec![format!("{} \\ {}", self.tier1, self.tier2)];
+        headers.extend(labels.iter().cloned());
+        writer.write_record(&headers)?;
+        for l1 in labels.iter() {
+            let mut row = vec![l1.to_owned()];
+            for l2 in labels.iter() {
+                let count = self.confusion.get(&(l1.to_owned(), l2.to_owned())).unwrap_or(&0);
+                row.push(count.to_string());
+            }
+            writer.write_record(&row)?;
+        }
+        let bytes = writer.into_inner().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        String::from_utf8(bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    }
+}
+
+fn truncate(s: &str, len: usize) -> String {
+    s.chars().take(len).collect()
+}
+
+/// Samples both tiers at the middle of each time slice,
+/// from the first annotation start to the last annotation end in either tier.
+/// Returns `(label tier 1, label tier 2)` for slices where
+/// at least one tier has an annotation.
+fn slice_pairs(segments1: &[Segment], segments2: &[Segment], slice_ms: i64) -> Vec<(String, String)> {
+    let start = segments1.iter().chain(segments2.iter()).map(|s| s.start).min();
+    let end = segments1.iter().chain(segments2.iter()).map(|s| s.end).max();
+    let (start, end) = match (start, end) {
+        (Some(s), Some(e)) => (s, e),
+        _ => return Vec::new()
+    };
+
+    let mut cursor1 = LabelCursor::new(segments1);
+    let mut cursor2 = LabelCursor::new(segments2);
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    let mut t = start;
+    while t < end {
+        let mid = t + slice_ms / 2;
+        match (cursor1.label_at(mid), cursor2.label_at(mid)) {
+            (None, None) => (),
+            (l1, l2) => pairs.push((
+                l1.unwrap_or(NONE_LABEL).to_owned(),
+                l2.unwrap_or(NONE_LABEL).to_owned()
+            ))
+        }
+        t += slice_ms;
+    }
+    pairs
+}
+
+/// Cohen's kappa for two raters, from the confusion matrix.
+/// Returns `None` if the matrix is empty.
+fn cohens_kappa(confusion: &Confusion) -> Option<f64> {
+    let n = confusion.values().sum::<usize>() as f64;
+    if n == 0. {
+        return None
+    }
+    let mut count1: BTreeMap<&str, f64> = BTreeMap::new();
+    let mut count2: BTreeMap<&str, f64> = BTreeMap::new();
+    let mut agreeing = 0.;
+    for ((l1, l2), count) in confusion.iter() {
+        *count1.entry(l1).or_insert(0.) += *count as f64;
+        *count2.entry(l2).or_insert(0.) += *count as f64;
+        if l1 == l2 {
+            agreeing += *count as f64;
+        }
+    }
+    let po = agreeing / n;
+    let pe: f64 = count1.iter()
+        .map(|(label, c1)| (c1 / n) * (count2.get(label).unwrap_or(&0.) / n))
+        .sum();
+    if pe >= 1. {
+        // Both raters use a single, identical category throughout
+        return Some(1.)
+    }
+    Some((po - pe) / (1. - pe))
+}
+
+/// Krippendorff's alpha for nominal data, two raters, no missing values,
+/// from the confusion matrix. Returns `None` if the matrix is empty.
+fn krippendorffs_alpha(confusion: &Confusion) -> Option<f64> {
+    if confusion.values().sum::<usize>() == 0 {
+        return None
+    }
+    // Coincidence matrix: each unit contributes both ordered value pairs
+    let mut coincidence: BTreeMap<(&str, &str), f64> = BTreeMap::new();
+    let mut totals: BTreeMap<&str, f64> = BTreeMap::new();
+    for ((l1, l2), count) in confusion.iter() {
+        let count = *count as f64;
+        *coincidence.entry((l1.as_str(), l2.as_str())).or_insert(0.) += count;
+        *coincidence.entry((l2.as_str(), l1.as_str())).or_insert(0.) += count;
+        *totals.entry(l1).or_insert(0.) += count;
+        *totals.entry(l2).or_insert(0.) += count;
+    }
+    let n: f64 = totals.values().sum();
+    let disagreement: f64 = coincidence.iter()
+        .filter(|((c, k), _)| c != k)
+        .map(|(_, o)| o)
+        .sum();
+    let expected: f64 = totals.iter()
+        .flat_map(|(c, nc)| totals.iter()
+            .filter(move |(k, _)| c != *k)
+            .map(move |(_, nk)| nc * nk)
+        )
+        .sum();
+    if expected == 0. {
+        // Only a single category in use
+        return Some(1.)
+    }
+    Some(1. - (n - 1.) * disagreement / expected)
+}
+
+/// Merges overlapping segments into non-overlapping time spans.
+fn spans(segments: &[Segment]) -> Vec<(i64, i64)> {
+    let mut spans: Vec<(i64, i64)> = Vec::new();
+    for s in segments.iter() {
+        match spans.last_mut() {
+            Some((_, end)) if s.start <= *end => *end = (*end).max(s.end),
+            _ => spans.push((s.start, s.end))
+        }
+    }
+    spans
+}
+
+/// Returns `(annotated time in both, annotated time in either)` in milliseconds.
+fn overlap(segments1: &[Segment], segments2: &[Segment]) -> (i64, i64) {
+    let spans1 = spans(segments1);
+    let spans2 = spans(segments2);
+    let total = |s: &[(i64, i64)]| s.iter().map(|(a, b)| b - a).sum::<i64>();
+
+    let mut both = 0;
+    for (s1, e1) in spans1.iter() {
+        for (s2, e2) in spans2.iter() {
+            let start = (*s1).max(*s2);
+            let end = (*e1).min(*e2);
+            if end > start {
+                both += end - start;
+            }
+        }
+    }
+
+    (both, total(&spans1) + total(&spans2) - both)
+}
+
+/// Annotation boundaries, i.e. unique start and end times, sorted.
+fn boundaries(segments: &[Segment]) -> Vec<i64> {
+    segments.iter()
+        .flat_map(|s| [s.start, s.end])
+        .collect::<BTreeSet<i64>>()
+        .into_iter()
+        .collect()
+}
+
+/// Returns the number of boundaries in `reference` that match
+/// a boundary in `other` within `tolerance` milliseconds.
+/// Each boundary is matched at most once, to the closest unmatched boundary.
+fn match_boundaries(reference: &[i64], other: &[i64], tolerance: i64) -> usize {
+    let mut matched = vec![false; other.len()];
+    let mut count = 0;
+    for b in reference.iter() {
+        let closest = other.iter()
+            .enumerate()
+            .filter(|(i, o)| !matched[*i] && (*o - b).abs() <= tolerance)
+            .min_by_key(|(_, o)| (*o - b).abs())
+            .map(|(i, _)| i);
+        if let Some(i) = closest {
+            matched[i] = true;
+            count += 1;
+        }
+    }
+    count
+}
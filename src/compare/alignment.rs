@@ -0,0 +1,118 @@
+//! Align annotations in two tiers on temporal overlap, rather than on index,
+//! so that a single extra annotation in either tier does not shift all
+//! following rows.
+//!
+//! Annotation pairs are matched greedily, in order of descending overlap,
+//! where each annotation can only be matched once. Annotations without
+//! an overlapping counterpart are deletions (first tier only),
+//! or insertions (second tier only).
+
+use super::agreement::Segment;
+
+/// A row of aligned annotations. At least one of `first`, `second` is set.
+#[derive(Debug, Clone)]
+pub struct Aligned<'a> {
+    pub first: Option<&'a Segment>,
+    pub second: Option<&'a Segment>,
+}
+
+impl<'a> Aligned<'a> {
+    /// Start time of the row, i.e. the earliest start time.
+    fn start(&self) -> i64 {
+        match (self.first, self.second) {
+            (Some(s1), Some(s2)) => s1.start.min(s2.start),
+            (Some(s), None) | (None, Some(s)) => s.start,
+            (None, None) => 0,
+        }
+    }
+
+    /// Annotation in first tier only.
+    pub fn is_deletion(&self) -> bool {
+        self.first.is_some() && self.second.is_none()
+    }
+
+    /// Annotation in second tier only.
+    pub fn is_insertion(&self) -> bool {
+        self.first.is_none() && self.second.is_some()
+    }
+
+    /// Boundary shifts in milliseconds `(start, end)`,
+    /// second tier relative to first tier.
+    pub fn shift(&self) -> Option<(i64, i64)> {
+        let (s1, s2) = (self.first?, self.second?);
+        Some((s2.start - s1.start, s2.end - s1.end))
+    }
+
+    /// Returns `true` if both annotations are set and their values differ.
+    pub fn value_differs(&self) -> bool {
+        match (self.first, self.second) {
+            (Some(s1), Some(s2)) => s1.label != s2.label,
+            _ => false
+        }
+    }
+
+    /// Marks describing the difference between the annotations,
+    /// e.g. `INS`, `DEL`, `SHIFT -20/+40`, `DIFF`.
+    /// Empty if the annotations are identical.
+    pub fn marks(&self) -> String {
+        if self.is_deletion() {
+            return "DEL".to_owned()
+        }
+        if self.is_insertion() {
+            return "INS".to_owned()
+        }
+        let mut marks: Vec<String> = Vec::new();
+        if let Some((start, end)) = self.shift().filter(|s| *s != (0, 0)) {
+            marks.push(format!("SHIFT {start:+}/{end:+}"));
+        }
+        if self.value_differs() {
+            marks.push("DIFF".to_owned());
+        }
+        marks.join(" ")
+    }
+}
+
+/// Overlap in milliseconds between two segments.
+fn overlap(s1: &Segment, s2: &Segment) -> i64 {
+    (s1.end.min(s2.end) - s1.start.max(s2.start)).max(0)
+}
+
+/// Aligns segments in `segments1` and `segments2` on maximal temporal overlap.
+/// Rows are sorted on start time.
+pub fn align<'a>(segments1: &'a [Segment], segments2: &'a [Segment]) -> Vec<Aligned<'a>> {
+    // Candidate pairs (overlap, index 1, index 2), largest overlap first,
+    // earlier annotations first on equal overlap
+    let mut candidates: Vec<(i64, usize, usize)> = segments1.iter().enumerate()
+        .flat_map(|(i, s1)| segments2.iter().enumerate()
+            .map(move |(j, s2)| (overlap(s1, s2), i, j))
+        )
+        .filter(|(o, _, _)| *o > 0)
+        .collect();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut matched1: Vec<Option<usize>> = vec![None; segments1.len()];
+    let mut matched2: Vec<bool> = vec![false; segments2.len()];
+    for (_, i, j) in candidates.into_iter() {
+        if matched1[i].is_none() && !matched2[j] {
+            matched1[i] = Some(j);
+            matched2[j] = true;
+        }
+    }
+
+    let mut rows: Vec<Aligned> = matched1.iter().enumerate()
+        .map(|(i, j)| Aligned {
+            first: Some(&segments1[i]),
+            second: j.map(|j| &segments2[j]),
+        })
+        .chain(matched2.iter().enumerate()
+            .filter(|(_, m)| !**m)
+            .map(|(j, _)| Aligned {
+                first: None,
+                second: Some(&segments2[j]),
+            })
+        )
+        .collect();
+    rows.sort_by_key(|r| r.start());
+
+    rows
+}
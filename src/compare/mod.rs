@@ -0,0 +1,538 @@
+//! Compare two tiers, either visually side by side, or via
+//! inter-annotator agreement metrics (see `agreement`).
+//! The compact view aligns annotations on temporal overlap (see `alignment`).
+//! Transcription tiers can also be scored via word and character error rates
+//! (see `wer`).
+//!
+//! Tiers can be in the same ELAN-file, or in two different ELAN-files,
+//! in which case tiers are paired by tier ID, or via a mapping file
+//! (see `merge::mapping`). In directory mode, ELAN-files in two directories
+//! are paired by file name and agreement is aggregated over all pairs.
+
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use regex::Regex;
+
+use eaf_rs::eaf::Eaf;
+
+use crate::{
+    eaf::{find_tier, resolve_tier},
+    files::{append_file_name, file_stem_as_string, has_extension, is_hidden, writefile, ErrorReport},
+    merge::mapping::TierMap,
+    text::{process_string, COMMON_PUNCTUATION},
+};
+
+pub mod agreement;
+pub mod alignment;
+pub mod wer;
+
+use agreement::{segments, Agreement};
+use alignment::align;
+use wer::{print_alignment, score, ErrorCount, Normalizer};
+
+pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
+
+    let eaf_paths: Vec<&PathBuf> = args.get_many::<PathBuf>("eaf")
+        .map(|v| v.collect())
+        .unwrap_or_default();
+    let dirs: Vec<&PathBuf> = args.get_many::<PathBuf>("dir")
+        .map(|v| v.collect())
+        .unwrap_or_default();
+
+    let mut compact = *args.get_one::<bool>("compact").unwrap();
+    let timeline = *args.get_one::<bool>("timeline").unwrap();
+    let max_len = *args.get_one::<usize>("max-length").unwrap(); // clap default 50
+    let tier_selectors: Vec<&str> = args.get_many::<String>("tier")
+        .map(|v| v.map(|s| s.as_str()).collect())
+        .unwrap_or_default();
+    let interactive = *args.get_one::<bool>("interactive").unwrap();
+    let agreement = *args.get_one::<bool>("agreement").unwrap();
+    let slice_ms = *args.get_one::<i64>("slice").unwrap(); // clap default 10
+    let tolerance_ms = *args.get_one::<i64>("tolerance").unwrap(); // clap default 200
+    let csv_path = args.get_one::<PathBuf>("csv"); // requires 'agreement'
+    let wer = *args.get_one::<bool>("wer").unwrap();
+    let show_alignment = *args.get_one::<bool>("alignment").unwrap();
+    let map = match args.get_one::<PathBuf>("map") {
+        Some(p) => Some(TierMap::read(p)?),
+        None => None
+    };
+
+    if slice_ms <= 0 {
+        let msg = format!("(!) 'slice' must be a positive integer.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
+
+    if !dirs.is_empty() {
+        if dirs.len() != 2 {
+            let msg = format!("(!) Specify exactly two directories via '--dir'.");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        }
+        return run_dirs(dirs[0], dirs[1], &tier_selectors, map.as_ref(), slice_ms, tolerance_ms, csv_path)
+    }
+
+    if eaf_paths.is_empty() || eaf_paths.len() > 2 {
+        let msg = format!("(!) Specify one or two ELAN-files via '--eaf'.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
+
+    if map.is_some() && eaf_paths.len() == 1 {
+        let msg = format!("(!) '--map' requires two ELAN-files.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
+
+    if !compact && !timeline && !agreement && !wer {
+        println!("SETTING COMPACT TO TRUE");
+        compact = true;
+    }
+
+    let eafs = eaf_paths.iter()
+        .map(|p| read_eaf(p))
+        .collect::<std::io::Result<Vec<Eaf>>>()?;
+    let eaf1 = &eafs[0];
+    let eaf2 = eafs.last().unwrap(); // same as eaf1 for a single file
+
+    // Two files, no tiers specified: pair all tiers by ID or mapping
+    if agreement && eafs.len() == 2 && tier_selectors.is_empty() && !interactive {
+        let pairs = tier_pairs(eaf1, eaf2, map.as_ref());
+        if pairs.is_empty() {
+            let msg = format!("(!) No tiers in '{}' and '{}' could be paired. Use '--tier' or '--map'.",
+                eaf_paths[0].display(),
+                eaf_paths[1].display()
+            );
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+        let results: Vec<Agreement> = pairs.iter()
+            .map(|(t1, t2)| tier_agreement(eaf1, eaf2, t1, t2, slice_ms, tolerance_ms))
+            .collect::<std::io::Result<Vec<Agreement>>>()?;
+        for (i, result) in results.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            result.print();
+        }
+        if let Some(p) = csv_path {
+            write_agreement_csv(&results, &results, p)?;
+        }
+        return Ok(())
+    }
+
+    let tier1 = match resolve_tier(eaf1, tier_selectors.get(0).copied(), interactive, false) {
+        Ok(t) => t,
+        Err(err) => {
+            let msg = format!("(!) Failed to extract tier: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    // For a second file, default to the tier paired with the first one
+    let selector2 = match (tier_selectors.get(1).copied(), eafs.len()) {
+        (Some(sel), _) => Some(sel.to_owned()),
+        (None, 2) if !interactive => tier_pairs(eaf1, eaf2, map.as_ref()).into_iter()
+            .find(|(t1, _)| t1 == &tier1.tier_id)
+            .map(|(_, t2)| t2),
+        _ => None
+    };
+
+    let tier2 = match resolve_tier(eaf2, selector2.as_deref(), interactive, false) {
+        Ok(t) => t,
+        Err(err) => {
+            let msg = format!("(!) Failed to extract tier: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    if agreement {
+        let result = Agreement::new(
+            &tier1.tier_id,
+            &tier2.tier_id,
+            &segments(eaf1, &tier1),
+            &segments(eaf2, &tier2),
+            slice_ms,
+            tolerance_ms
+        );
+        result.print();
+        if let Some(p) = csv_path {
+            write_agreement_csv(&[result.to_owned()], &[result], p)?;
+        }
+        return Ok(())
+    }
+
+    if wer {
+        let mut remove = match *args.get_one::<bool>("remove-common").unwrap() {
+            true => Some(COMMON_PUNCTUATION.to_owned()),
+            false => None
+        };
+        if let Some(custom) = args.get_one::<String>("remove-custom") {
+            remove = Some(format!("{}[{}]", remove.map(|r| format!("{r}|")).unwrap_or_default(), regex::escape(custom)));
+        }
+        let normalizer = Normalizer {
+            ignore_case: *args.get_one::<bool>("ignore-case").unwrap(),
+            remove: match remove.map(|r| Regex::new(&r)) {
+                Some(Ok(rx)) => Some(rx),
+                Some(Err(err)) => {
+                    let msg = format!("(!) Regex error: {err}");
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                },
+                None => None
+            }
+        };
+        print_error_rates(&tier1.tier_id, &tier2.tier_id,
+            &score(&segments(eaf1, &tier1), &segments(eaf2, &tier2), &normalizer),
+            show_alignment
+        );
+        return Ok(())
+    }
+
+    if eaf_paths.len() == 2 {
+        println!("Left:  {}", eaf_paths[0].display());
+        println!("Right: {}", eaf_paths[1].display());
+    }
+
+    let line_len = if compact {max_len * 2 + 64} else {max_len * 2 + 30};
+    let len = if compact {max_len + 18} else {max_len + 5};
+    println!("{}", ".".repeat(line_len));
+    let pad = if compact {8} else {23};
+    println!("  {:>len$}{}{}", tier1.tier_id, " ".repeat(pad), tier2.tier_id);
+    println!("{}", ".".repeat(line_len));
+
+    if timeline {
+
+        // Annotations are tagged with side (1: left, 2: right),
+        // since tiers in different files may share the same tier ID
+        let mut all_annotations: Vec<_> = tier1.iter().map(|a| (1, a))
+            .chain(tier2.iter().map(|a| (2, a)))
+            .map(|(side, a)| {
+                let (ts1, ts2) = match a.ts_val() {
+                    (Some(t1), Some(t2)) => (t1, t2),
+                    _ => {
+                        let msg = format!("(!) Missing time value for annotation with ID '{}'", a.id());
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                    }
+                };
+                Ok((side, ts1, ts2, a.to_str()))
+            })
+            .collect::<std::io::Result<Vec<(u8, i64, i64, &str)>>>()?;
+
+        // Sort annotations on start time value
+        all_annotations.sort_by_key(|a| a.1);
+
+        for (i, a) in all_annotations.iter().enumerate() {
+            // match on side to print to the left or right
+            // (side, t1, t2, value)
+            match a.0 {
+                1 => {
+                    println!("{:04} | {:>max_len$} |{:8} - {:<8}|",
+                        i + 1,
+                        process_string(&a.3, None, None, None, Some(max_len)),
+                        a.1,
+                        a.2,
+                    );
+                },
+                _ => {
+                    println!("{:04} | {} |{:8} - {:<8}| {:<max_len$}",
+                        i + 1,
+                        " ".repeat(max_len),
+                        a.1,
+                        a.2,
+                        process_string(&a.3, None, None, None, Some(max_len)),
+                    );
+                },
+            }
+        };
+    }
+
+    if compact {
+        let segments1 = segments(eaf1, &tier1);
+        let segments2 = segments(eaf2, &tier2);
+        let rows = align(&segments1, &segments2);
+
+        for (i, row) in rows.iter().enumerate() {
+            let left = match row.first {
+                Some(s) => format!("{:>8} - {:<8} {:>max_len$}",
+                    s.start,
+                    s.end,
+                    process_string(&s.label, None, None, None, Some(max_len)),
+                ),
+                None => " ".repeat(max_len + 20)
+            };
+            let right = match row.second {
+                Some(s) => format!("{:<max_len$} {:>8} - {:<8}",
+                    process_string(&s.label, None, None, None, Some(max_len)),
+                    s.start,
+                    s.end,
+                ),
+                None => " ".repeat(max_len + 20)
+            };
+            println!("{left} |{:04}| {right} {}", i + 1, row.marks());
+        }
+
+        println!("{}", ".".repeat(line_len));
+        println!("Aligned: {} | Shifted: {} | Value differs: {} | Deleted: {} | Inserted: {}",
+            rows.iter().filter(|r| r.first.is_some() && r.second.is_some()).count(),
+            rows.iter().filter(|r| r.shift().map(|s| s != (0, 0)).unwrap_or(false)).count(),
+            rows.iter().filter(|r| r.value_differs()).count(),
+            rows.iter().filter(|r| r.is_deletion()).count(),
+            rows.iter().filter(|r| r.is_insertion()).count(),
+        );
+        let untimed = tier1.len() + tier2.len() - segments1.len() - segments2.len();
+        if untimed > 0 {
+            println!("Skipped {untimed} annotations without time values.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints word and character error rates per segment and overall,
+/// optionally with the word alignment for each segment with errors.
+fn print_error_rates(reference: &str, hypothesis: &str, scored: &[wer::ScoredSegment], show_alignment: bool) {
+    let opt = |v: Option<f64>| v.map(|n| format!("{n:.4}")).unwrap_or("NA".to_owned());
+
+    println!("[ Error rates ] '{hypothesis}' (hypothesis) vs '{reference}' (reference)");
+    println!("{:>4} {:>9} - {:<9} {:>6} {:>4} {:>4} {:>4} {:>8} {:>8}", "#", "Start", "End", "Words", "S", "D", "I", "WER", "CER");
+    let mut words = ErrorCount::default();
+    let mut chars = ErrorCount::default();
+    for (i, segment) in scored.iter().enumerate() {
+        println!("{:>4} {:>9} - {:<9} {:>6} {:>4} {:>4} {:>4} {:>8} {:>8}{}",
+            i + 1,
+            segment.start,
+            segment.end,
+            segment.words.reference_len,
+            segment.words.substitutions,
+            segment.words.deletions,
+            segment.words.insertions,
+            opt(segment.words.rate()),
+            opt(segment.chars.rate()),
+            if segment.has_reference {""} else {" (no reference)"},
+        );
+        if show_alignment && segment.words.errors() > 0 {
+            print_alignment(&segment.word_edits);
+        }
+        words.add(&segment.words);
+        chars.add(&segment.chars);
+    }
+
+    println!("[ Overall ]");
+    println!("  Segments             | {}", scored.len());
+    println!("  Reference words      | {}", words.reference_len);
+    println!("  Substitutions        | {}", words.substitutions);
+    println!("  Deletions            | {}", words.deletions);
+    println!("  Insertions           | {}", words.insertions);
+    println!("  WER                  | {}", opt(words.rate()));
+    println!("  CER                  | {}", opt(chars.rate()));
+}
+
+/// Reads ELAN-file at `path`.
+fn read_eaf(path: &Path) -> std::io::Result<Eaf> {
+    match Eaf::read(path) {
+        Ok(f) => Ok(f),
+        Err(err) => {
+            let msg = format!("(!) Error parsing '{}': {err}", path.display());
+            Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    }
+}
+
+/// Returns `(tier ID in eaf1, tier ID in eaf2)` for tiers in `eaf1`
+/// with a counterpart in `eaf2`, either via `map`, or with the same tier ID
+/// if no rule matches.
+fn tier_pairs(eaf1: &Eaf, eaf2: &Eaf, map: Option<&TierMap>) -> Vec<(String, String)> {
+    eaf1.tiers.iter()
+        .map(|t| {
+            let target = map
+                .and_then(|m| m.target(&t.tier_id))
+                .unwrap_or_else(|| t.tier_id.to_owned());
+            (t.tier_id.to_owned(), target)
+        })
+        .filter(|(_, target)| eaf2.get_tier(target).is_some())
+        .collect()
+}
+
+/// Agreement between tier `tier_id1` in `eaf1` and `tier_id2` in `eaf2`.
+fn tier_agreement(
+    eaf1: &Eaf,
+    eaf2: &Eaf,
+    tier_id1: &str,
+    tier_id2: &str,
+    slice_ms: i64,
+    tolerance_ms: i64
+) -> std::io::Result<Agreement> {
+    let tier1 = find_tier(eaf1, tier_id1, false)?;
+    let tier2 = find_tier(eaf2, tier_id2, false)?;
+    Ok(Agreement::new(
+        &tier1.tier_id,
+        &tier2.tier_id,
+        &segments(eaf1, &tier1),
+        &segments(eaf2, &tier2),
+        slice_ms,
+        tolerance_ms
+    ))
+}
+
+/// Returns sorted, non-hidden ELAN-files in `dir`.
+fn eaf_paths(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = dir.read_dir()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| has_extension(p, "eaf") && !is_hidden(p))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Compares ELAN-files in `dir1` and `dir2` paired by file stem,
+/// printing agreement per file pair, then pooled per tier pair
+/// over the whole corpus.
+fn run_dirs(
+    dir1: &Path,
+    dir2: &Path,
+    tier_selectors: &[&str],
+    map: Option<&TierMap>,
+    slice_ms: i64,
+    tolerance_ms: i64,
+    csv_path: Option<&PathBuf>
+) -> std::io::Result<()> {
+    let paths1 = eaf_paths(dir1)?;
+    let paths2 = eaf_paths(dir2)?;
+
+    let mut file_pairs: Vec<(String, &PathBuf, &PathBuf)> = Vec::new();
+    let mut unpaired: Vec<&PathBuf> = Vec::new();
+    for p1 in paths1.iter() {
+        let stem = file_stem_as_string(p1).unwrap_or_default();
+        match paths2.iter().find(|p2| file_stem_as_string(p2).as_deref() == Some(stem.as_str())) {
+            Some(p2) => file_pairs.push((stem, p1, p2)),
+            None => unpaired.push(p1)
+        }
+    }
+    unpaired.extend(paths2.iter()
+        .filter(|p2| !file_pairs.iter().any(|(_, _, p)| p == p2)));
+
+    if file_pairs.is_empty() {
+        let msg = format!("(!) No ELAN-files in '{}' and '{}' with the same file name.", dir1.display(), dir2.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
+
+    let mut errors = ErrorReport::new();
+    let mut results: Vec<Agreement> = Vec::new();
+
+    println!("{:<24} {:<20} {:<20} {:>8} {:>8} {:>8}", "File", "Tier 1", "Tier 2", "Kappa", "Alpha", "F-score");
+    println!("{}", "-".repeat(93));
+    let opt = |v: Option<f64>| v.map(|n| format!("{n:.4}")).unwrap_or("NA".to_owned());
+
+    for (stem, p1, p2) in file_pairs.iter() {
+        let (eaf1, eaf2) = match (read_eaf(p1), read_eaf(p2)) {
+            (Ok(e1), Ok(e2)) => (e1, e2),
+            (Err(err), _) => {
+                errors.add(p1, &err);
+                continue
+            },
+            (_, Err(err)) => {
+                errors.add(p2, &err);
+                continue
+            },
+        };
+
+        let pairs = match tier_selectors.first() {
+            Some(sel1) => {
+                let sel2 = tier_selectors.get(1).unwrap_or(sel1);
+                match (find_tier(&eaf1, sel1, false), find_tier(&eaf2, sel2, false)) {
+                    (Ok(t1), Ok(t2)) => vec![(t1.tier_id, t2.tier_id)],
+                    (Err(err), _) | (_, Err(err)) => {
+                        errors.add(p1, &err);
+                        continue
+                    }
+                }
+            },
+            None => tier_pairs(&eaf1, &eaf2, map)
+        };
+
+        if pairs.is_empty() {
+            errors.add(p1, &"No tiers could be paired");
+            continue
+        }
+
+        for (t1, t2) in pairs.iter() {
+            match tier_agreement(&eaf1, &eaf2, t1, t2, slice_ms, tolerance_ms) {
+                Ok(result) => {
+                    println!("{:<24} {:<20} {:<20} {:>8} {:>8} {:>8}",
+                        stem,
+                        t1,
+                        t2,
+                        opt(result.kappa),
+                        opt(result.alpha),
+                        opt(result.f_score()),
+                    );
+                    results.push(result.with_source(stem));
+                },
+                Err(err) => errors.add(p1, &err)
+            }
+        }
+    }
+
+    // Pool per tier pair, then over all tier pairs if there are several
+    let tier_ids: Vec<(String, String)> = results.iter()
+        .map(|a| (a.tier1.to_owned(), a.tier2.to_owned()))
+        .unique()
+        .collect();
+    let mut pooled: Vec<Agreement> = tier_ids.iter()
+        .map(|(t1, t2)| {
+            let group: Vec<Agreement> = results.iter()
+                .filter(|a| &a.tier1 == t1 && &a.tier2 == t2)
+                .cloned()
+                .collect();
+            Agreement::pooled("ALL", t1, t2, &group)
+        })
+        .collect();
+    if pooled.len() > 1 {
+        pooled.push(Agreement::pooled("ALL", "*", "*", &results));
+    }
+
+    for result in pooled.iter() {
+        println!();
+        result.print();
+    }
+
+    if !unpaired.is_empty() {
+        println!("\nSkipped {} files without a counterpart:", unpaired.len());
+        for path in unpaired.iter() {
+            println!("  {}", path.display());
+        }
+    }
+
+    println!();
+    errors.print(file_pairs.len());
+
+    if let Some(p) = csv_path {
+        let rows: Vec<Agreement> = results.iter().chain(pooled.iter()).cloned().collect();
+        write_agreement_csv(&rows, &pooled, p)?;
+    }
+
+    Ok(())
+}
+
+/// Writes agreement metrics for `agreements` to `path`, one row each,
+/// and the confusion matrix for each of `confusion` to `path`
+/// with the suffix `_confusion` (and the pair number if there are several).
+fn write_agreement_csv(agreements: &[Agreement], confusion: &[Agreement], path: &Path) -> std::io::Result<()> {
+    let mut files: Vec<(PathBuf, String)> = vec![(path.to_owned(), Agreement::metrics_csv(agreements, b',')?)];
+    for (i, agreement) in confusion.iter().enumerate() {
+        let suffix = match confusion.len() {
+            1 => "confusion".to_owned(),
+            _ => format!("confusion_{}", i+1)
+        };
+        files.push((append_file_name(path, &suffix), agreement.confusion_csv(b',')?));
+    }
+
+    for (csv_path, csv) in files {
+        match writefile(csv.as_bytes(), &csv_path) {
+            Ok(Some(p)) => println!("Wrote {}", p.display()),
+            Ok(None) => println!("Skipped writing {}", csv_path.display()),
+            Err(err) => {
+                let msg = format!("(!) Failed to write '{}': {err}", csv_path.display());
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        }
+    }
+
+    Ok(())
+}
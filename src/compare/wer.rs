@@ -0,0 +1,240 @@
+//! Word and character error rates (WER, CER) for a hypothesis tier,
+//! e.g. the `segments` or `words` tier generated by `whisper2eaf`,
+//! relative to a reference tier, e.g. a hand-corrected copy.
+//!
+//! Each hypothesis annotation is assigned to the reference annotation
+//! it overlaps the most. Hypothesis annotations without an overlapping reference
+//! annotation form segments of their own, where every token is an insertion.
+//! Error rates are calculated per reference segment, and overall as the sum
+//! of all errors relative to the total reference length.
+
+use regex::Regex;
+
+use crate::text::process_string;
+
+use super::agreement::Segment;
+
+/// Text normalisation before tokenization.
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    pub ignore_case: bool,
+    /// Characters to remove, e.g. punctuation.
+    pub remove: Option<Regex>,
+}
+
+impl Normalizer {
+    /// Returns whitespace delimited word tokens in `value` after normalisation.
+    pub fn words(&self, value: &str) -> Vec<String> {
+        let mut string = process_string(value, None, None, self.remove.as_ref(), None);
+        if self.ignore_case {
+            string = string.to_lowercase();
+        }
+        string.split_whitespace()
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Edit operation for aligning a hypothesis token sequence to a reference.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Match(String),
+    /// `(reference, hypothesis)`
+    Substitution(String, String),
+    /// Reference token missing in hypothesis.
+    Deletion(String),
+    /// Hypothesis token missing in reference.
+    Insertion(String),
+}
+
+/// Minimum edit distance alignment of `hypothesis` to `reference`
+/// (Levenshtein, all operations with cost 1).
+pub fn edits(reference: &[String], hypothesis: &[String]) -> Vec<Edit> {
+    let (n, m) = (reference.len(), hypothesis.len());
+
+    // Cost matrix, (n + 1) x (m + 1)
+    let mut d = vec![vec![0usize; m + 1]; n + 1];
+    for i in 0..=n {
+        d[i][0] = i;
+    }
+    for j in 0..=m {
+        d[0][j] = j;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let sub = d[i-1][j-1] + (reference[i-1] != hypothesis[j-1]) as usize;
+            d[i][j] = sub.min(d[i-1][j] + 1).min(d[i][j-1] + 1);
+        }
+    }
+
+    // Backtrace, preferring match/substitution over deletion over insertion
+    let mut edits: Vec<Edit> = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && d[i][j] == d[i-1][j-1] + (reference[i-1] != hypothesis[j-1]) as usize {
+            edits.push(match reference[i-1] == hypothesis[j-1] {
+                true => Edit::Match(reference[i-1].to_owned()),
+                false => Edit::Substitution(reference[i-1].to_owned(), hypothesis[j-1].to_owned()),
+            });
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && d[i][j] == d[i-1][j] + 1 {
+            edits.push(Edit::Deletion(reference[i-1].to_owned()));
+            i -= 1;
+        } else {
+            edits.push(Edit::Insertion(hypothesis[j-1].to_owned()));
+            j -= 1;
+        }
+    }
+    edits.reverse();
+
+    edits
+}
+
+/// Error counts for an alignment.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorCount {
+    pub substitutions: usize,
+    pub deletions: usize,
+    pub insertions: usize,
+    /// Number of tokens in reference.
+    pub reference_len: usize,
+}
+
+impl ErrorCount {
+    pub fn new(edits: &[Edit]) -> Self {
+        let mut count = Self::default();
+        for edit in edits.iter() {
+            match edit {
+                Edit::Match(_) => count.reference_len += 1,
+                Edit::Substitution(..) => {
+                    count.substitutions += 1;
+                    count.reference_len += 1;
+                },
+                Edit::Deletion(_) => {
+                    count.deletions += 1;
+                    count.reference_len += 1;
+                },
+                Edit::Insertion(_) => count.insertions += 1,
+            }
+        }
+        count
+    }
+
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.deletions + self.insertions
+    }
+
+    /// Error rate, `(S + D + I) / N`. `None` if the reference is empty.
+    /// May exceed 1.0 if there are many insertions.
+    pub fn rate(&self) -> Option<f64> {
+        match self.reference_len {
+            0 => None,
+            n => Some(self.errors() as f64 / n as f64)
+        }
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        self.substitutions += other.substitutions;
+        self.deletions += other.deletions;
+        self.insertions += other.insertions;
+        self.reference_len += other.reference_len;
+    }
+}
+
+/// Scored segment.
+#[derive(Debug, Clone)]
+pub struct ScoredSegment {
+    pub start: i64,
+    pub end: i64,
+    /// `true` if there is a reference annotation for this segment.
+    pub has_reference: bool,
+    pub word_edits: Vec<Edit>,
+    pub words: ErrorCount,
+    pub chars: ErrorCount,
+}
+
+/// Groups `hypothesis` segments under the `reference` segment
+/// they overlap the most. Hypothesis segments without overlap
+/// are returned as separate groups without reference.
+fn group<'a>(reference: &'a [Segment], hypothesis: &'a [Segment]) -> Vec<(Option<&'a Segment>, Vec<&'a Segment>)> {
+    let mut groups: Vec<(Option<&Segment>, Vec<&Segment>)> = reference.iter()
+        .map(|r| (Some(r), Vec::new()))
+        .collect();
+    let mut unassigned: Vec<(Option<&Segment>, Vec<&Segment>)> = Vec::new();
+
+    for hyp in hypothesis.iter() {
+        let best = reference.iter().enumerate()
+            .map(|(i, r)| (i, r.end.min(hyp.end) - r.start.max(hyp.start)))
+            .filter(|(_, overlap)| *overlap > 0)
+            .max_by_key(|(i, overlap)| (*overlap, std::cmp::Reverse(*i)));
+        match best {
+            Some((i, _)) => groups[i].1.push(hyp),
+            None => unassigned.push((None, vec![hyp]))
+        }
+    }
+
+    groups.extend(unassigned);
+    groups.sort_by_key(|(r, h)| r.or(h.first().copied()).map(|s| s.start));
+    groups
+}
+
+/// Scores `hypothesis` against `reference`, per segment.
+pub fn score(reference: &[Segment], hypothesis: &[Segment], normalizer: &Normalizer) -> Vec<ScoredSegment> {
+    group(reference, hypothesis).into_iter()
+        .map(|(r, h)| {
+            let ref_words: Vec<String> = r.map(|s| normalizer.words(&s.label)).unwrap_or_default();
+            let hyp_words: Vec<String> = h.iter()
+                .flat_map(|s| normalizer.words(&s.label))
+                .collect();
+            let word_edits = edits(&ref_words, &hyp_words);
+            let char_edits = edits(&chars(&ref_words), &chars(&hyp_words));
+
+            let (start, end) = match r {
+                Some(s) => (s.start, s.end),
+                None => (
+                    h.iter().map(|s| s.start).min().unwrap_or_default(),
+                    h.iter().map(|s| s.end).max().unwrap_or_default()
+                )
+            };
+
+            ScoredSegment {
+                start,
+                end,
+                has_reference: r.is_some(),
+                words: ErrorCount::new(&word_edits),
+                chars: ErrorCount::new(&char_edits),
+                word_edits,
+            }
+        })
+        .collect()
+}
+
+/// Characters in `words` joined by single spaces, as tokens.
+fn chars(words: &[String]) -> Vec<String> {
+    words.join(" ").chars()
+        .map(String::from)
+        .collect()
+}
+
+/// Prints `edits` as reference and hypothesis rows with
+/// substitutions (S), deletions (D) and insertions (I) marked below,
+/// with `***` for missing tokens.
+pub fn print_alignment(edits: &[Edit]) {
+    let mut rows = [String::from("  REF:"), String::from("  HYP:"), String::from("      ")];
+    for edit in edits.iter() {
+        let (r, h, mark) = match edit {
+            Edit::Match(t) => (t.as_str(), t.as_str(), ""),
+            Edit::Substitution(r, h) => (r.as_str(), h.as_str(), "S"),
+            Edit::Deletion(r) => (r.as_str(), "***", "D"),
+            Edit::Insertion(h) => ("***", h.as_str(), "I"),
+        };
+        let width = r.chars().count().max(h.chars().count());
+        rows[0].push_str(&format!(" {r:<width$}"));
+        rows[1].push_str(&format!(" {h:<width$}"));
+        rows[2].push_str(&format!(" {mark:<width$}"));
+    }
+    for row in rows.iter() {
+        println!("{}", row.trim_end());
+    }
+}
@@ -1,20 +1,45 @@
 //! Generate an ELAN-file from a CSV-file with annotations.
 //! Delimiter options are: comma (default), tab, or semi-colon.
 //! One row per annotation. Requires a column denoting start time, end time,
-//! and annotation value in chronological order (not checked).
+//! and annotation value, in chronological order per tier.
 //! Time stamps can be either millseconds as a positive integer,
 //! or HH:MM:SS.fff - sub-seconds `fff` are optional.
-
-use std::{path::PathBuf, collections::{HashMap, HashSet}, error::Error};
+//!
+//! Columns specified with `--refs` become referred tiers with the
+//! symbolic association stereotype, one annotation per row, with the
+//! annotation on the same row in the main tier as parent. A ref column
+//! can instead refer to another ref column via `COLUMN:PARENT_COLUMN`,
+//! for nested referred tiers. Empty cells are skipped.
+//!
+//! A global offset (`--offset`), and optionally a per-row offset from a column
+//! (`--offset-column`), are added to all time stamps, e.g. for time stamps
+//! relative to the start of a clip. Rows with negative time stamps after
+//! offset raise an error listing their line numbers, unless `--clamp` is set.
+//!
+//! All rows are validated before generating the ELAN-file. Every problem is
+//! listed with its line number: missing columns, invalid time stamps,
+//! end times not after start times, out-of-order rows, and overlapping rows
+//! within a tier. With `--fix`, rows are instead sorted on start time,
+//! and invalid rows are dropped.
+//!
+//! Spreadsheets (`.xlsx`, `.xlsm`, `.xlsb`, `.xls`, `.ods`) are read directly,
+//! with the same column options, using the first sheet unless `--sheet` is set.
+//! Native time and duration cells are converted to milliseconds as is,
+//! other cells, including numbers, are parsed as text.
+
+use std::{path::{Path, PathBuf}, collections::{HashMap, HashSet}, error::Error};
 
 use csv::{self, Trim};
 use itertools::Itertools;
 use time::Duration;
 
-use eaf_rs::{eaf::{Eaf, Tier, StereoType}, EafError, LinguisticType};
+use eaf_rs::{eaf::{Eaf, Tier, StereoType, annotation::AnnotationBuilder}, Annotation, EafError, LinguisticType};
 use rttm_rs::{Rttm, RttmSegment};
 
-use crate::files::writefile;
+use crate::{
+    files::{create_parent, writefile},
+    spreadsheet::{self, is_spreadsheet, Cell},
+};
 
 /// Takes a string representing a duration in the form
 /// `HH:MM:SS` (hours:minutes:seconds),
@@ -22,7 +47,7 @@ use crate::files::writefile;
 /// `HH:MM:SS,fff` (hours:minutes:seconds.sub-seconds)
 /// and returns `chrono::Duration`. E.g. `00:03:54` or `00:03:54.234`.
 /// Sub-seconds are optional.
-fn hms2duration(hmsf: &str) -> Result<Duration, Box<dyn Error>> { // Box<dyn Error> for parse int + float errors...
+pub fn hms2duration(hmsf: &str) -> Result<Duration, Box<dyn Error>> { // Box<dyn Error> for parse int + float errors...
     let mut duration = Duration::hours(0);
     // split should have len 3 or 4 (.count() consumes iterator)
     // TODO perhaps check len of .split(":") and if 2 assume MM:SS, rather than HH:MM?
@@ -34,7 +59,7 @@ fn hms2duration(hmsf: &str) -> Result<Duration, Box<dyn Error>> { // Box<dyn Err
                 // Parse to float and add as milliseconds
                 // to catch sec + sub-second value if '.fff' or ',fff' present
                 let t: f64 = value.trim().replace(",", ".").parse()?;
-                duration = duration + Duration::milliseconds((t * 1000.0) as i64);
+                duration = duration + Duration::milliseconds((t * 1000.0).round() as i64);
             },
             _ => break
         }
@@ -60,6 +85,319 @@ fn string2ms(value: &str) -> Option<i64> {
     }
 }
 
+/// Takes a possibly signed time stamp, e.g. `-1500` or `-00:00:01.5`,
+/// and returns milliseconds.
+fn string2offset(value: &str) -> Option<i64> {
+    match value.strip_prefix('-') {
+        Some(v) => string2ms(v.trim()).map(|ms| -ms),
+        None => string2ms(value.trim_start_matches('+'))
+    }
+}
+
+/// Reads column headers, and rows with line numbers, from a CSV-file.
+fn read_csv<R: std::io::Read>(reader: &mut csv::Reader<R>) -> std::io::Result<(Vec<String>, Vec<(usize, Vec<Cell>)>)> {
+    let headers: Vec<String> = reader.headers()?.iter()
+        .map(String::from)
+        .collect();
+    let mut rows: Vec<(usize, Vec<Cell>)> = Vec::new();
+    for result in reader.records() {
+        let record = result?; // one row
+        // Line number in CSV-file, for reporting
+        let line = record.position().map(|p| p.line() as usize).unwrap_or_default();
+        rows.push((line, record.iter().map(|c| Cell::Text(c.to_owned())).collect()));
+    }
+    Ok((headers, rows))
+}
+
+/// Annotation row in CSV-file.
+#[derive(Debug, Clone)]
+struct Row {
+    /// Line number in CSV-file.
+    line: usize,
+    value: String,
+    tier_id: String,
+    /// Start and end in milliseconds, with offset applied.
+    /// `None` if a time stamp is invalid.
+    timespan: Option<(i64, i64)>,
+    /// Values for referred tiers. Key: ref column.
+    refs: HashMap<String, String>,
+}
+
+/// Problem found when validating CSV rows.
+#[derive(Debug, Clone)]
+enum Issue {
+    MissingColumn(String),
+    InvalidTime { column: String, value: String },
+    EndBeforeStart { start: i64, end: i64 },
+    Negative { start: i64, end: i64 },
+    /// Starts before an earlier row in the same tier.
+    OutOfOrder { previous: usize },
+    /// Overlaps an earlier row in the same tier.
+    Overlap { previous: usize },
+}
+
+impl Issue {
+    /// Returns `true` if the row is dropped with `--fix`.
+    /// Out-of-order rows are sorted instead.
+    fn drops_row(&self) -> bool {
+        !matches!(self, Self::OutOfOrder { .. })
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingColumn(col) => write!(f, "No column named '{col}'"),
+            Self::InvalidTime { column, value } => write!(f, "{column}: Failed to convert '{value}' to milliseconds"),
+            Self::EndBeforeStart { start, end } => write!(f, "End time {end} ms is not after start time {start} ms"),
+            Self::Negative { start, end } => write!(f, "Negative time stamps after offset ({start} - {end} ms)"),
+            Self::OutOfOrder { previous } => write!(f, "Starts before the row on line {previous} in the same tier"),
+            Self::Overlap { previous } => write!(f, "Overlaps the row on line {previous} in the same tier"),
+        }
+    }
+}
+
+/// Adds `offset` to `t1` and `t2`. Negative results are returned
+/// as an issue, unless `clamp` is set, in which case start is set to 0 ms,
+/// and rows ending at or before 0 ms are discarded (`Ok(None)`).
+fn apply_offset(t1: i64, t2: i64, offset: i64, clamp: bool) -> Result<Option<(i64, i64)>, Issue> {
+    let (t1, t2) = (t1 + offset, t2 + offset);
+    if t1 >= 0 && t2 >= 0 {
+        return Ok(Some((t1, t2)))
+    }
+    match (clamp, t2 <= 0) {
+        (false, _) => Err(Issue::Negative { start: t1, end: t2 }),
+        (true, true) => Ok(None),
+        (true, false) => Ok(Some((0, t2)))
+    }
+}
+
+/// Checks that rows end after they start, and that rows within each tier
+/// are chronological and do not overlap. Adds any problems to `issues`,
+/// with line numbers. Returns rows with valid time stamps, sorted on start
+/// time, excluding rows that overlap an earlier row in the same tier.
+fn validate(rows: Vec<Row>, issues: &mut Vec<(usize, Issue)>) -> Vec<Row> {
+    let mut valid: Vec<Row> = Vec::new();
+    for row in rows.into_iter() {
+        match row.timespan {
+            Some((t1, t2)) if t2 <= t1 => issues.push((row.line, Issue::EndBeforeStart { start: t1, end: t2 })),
+            Some(_) => valid.push(row),
+            None => () // invalid time stamps logged when parsed
+        }
+    }
+
+    // Latest start time per tier in file order, and its line number
+    let mut latest: HashMap<&str, (i64, usize)> = HashMap::new();
+    for row in valid.iter() {
+        let (t1, _) = row.timespan.unwrap_or_default();
+        match latest.get(row.tier_id.as_str()) {
+            Some((start, line)) if t1 < *start => issues.push((row.line, Issue::OutOfOrder { previous: *line })),
+            _ => { latest.insert(&row.tier_id, (t1, row.line)); }
+        }
+    }
+
+    // Stable sort, rows with the same start time retain file order
+    valid.sort_by_key(|r| r.timespan.unwrap_or_default());
+
+    // End time of the last retained row per tier, and its line number
+    let mut last: HashMap<String, (i64, usize)> = HashMap::new();
+    let mut sorted: Vec<Row> = Vec::new();
+    for row in valid.into_iter() {
+        let (t1, t2) = row.timespan.unwrap_or_default();
+        if let Some((end, line)) = last.get(&row.tier_id) {
+            if t1 < *end {
+                issues.push((row.line, Issue::Overlap { previous: *line }));
+                continue
+            }
+        }
+        last.insert(row.tier_id.to_owned(), (t2, row.line));
+        sorted.push(row);
+    }
+
+    sorted
+}
+
+/// Prints all issues in line order.
+fn print_issues(issues: &[(usize, Issue)], path: &Path) {
+    println!("[ Validation ] {} problems in '{}'", issues.len(), path.display());
+    for (line, issue) in issues.iter().sorted_by_key(|(l, _)| *l) {
+        println!("  Line {line:>6}: {issue}");
+    }
+}
+
+/// Linguistic type for referred tiers generated from ref columns.
+const REF_LINGUISTIC_TYPE: &str = "ref-tier-symbolic-association";
+
+/// Column with values for a referred tier.
+#[derive(Debug, Clone)]
+struct RefColumn {
+    column: String,
+    /// Ref column for the parent tier, or `None` if the parent is the main tier.
+    parent: Option<String>,
+}
+
+impl RefColumn {
+    /// Parses `COLUMN` or `COLUMN:PARENT_COLUMN`.
+    fn from_str(spec: &str) -> Self {
+        match spec.split_once(':') {
+            Some((column, parent)) => Self {
+                column: column.trim().to_owned(),
+                parent: Some(parent.trim().to_owned())
+            },
+            None => Self {
+                column: spec.trim().to_owned(),
+                parent: None
+            }
+        }
+    }
+}
+
+/// Returns `ref_columns` ordered so that each column follows its parent column.
+fn ordered_ref_columns(ref_columns: &[RefColumn]) -> std::io::Result<Vec<RefColumn>> {
+    let mut ordered: Vec<RefColumn> = Vec::new();
+    while ordered.len() < ref_columns.len() {
+        let ready: Vec<RefColumn> = ref_columns.iter()
+            .filter(|c| !ordered.iter().any(|o| o.column == c.column))
+            .filter(|c| match &c.parent {
+                Some(p) => ordered.iter().any(|o| &o.column == p),
+                None => true
+            })
+            .cloned()
+            .collect();
+        if ready.is_empty() {
+            let unresolved: Vec<String> = ref_columns.iter()
+                .filter(|c| !ordered.iter().any(|o| o.column == c.column))
+                .map(|c| format!("{}:{}", c.column, c.parent.as_deref().unwrap_or_default()))
+                .collect();
+            let msg = format!("(!) Parent columns must also be specified with '--refs', and can not be circular: {}", unresolved.join(", "));
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        }
+        ordered.extend(ready);
+    }
+    Ok(ordered)
+}
+
+/// Adds one referred tier per ref column and main tier to `eaf`.
+/// `ref_rows` contains the ref column values for each row, in the same order
+/// as `main_annotations`, which were used to generate `eaf`.
+fn add_ref_tiers(
+    eaf: &mut Eaf,
+    main_annotations: &[(String, i64, i64, String)],
+    ref_rows: &[HashMap<String, String>],
+    ref_columns: &[RefColumn],
+) -> std::io::Result<()> {
+    let main_tier_ids: Vec<String> = main_annotations.iter()
+        .map(|(_, _, _, id)| id.to_owned())
+        .unique()
+        .collect();
+
+    // Main tier annotation IDs per (tier ID, start, end), in tier order
+    let mut spans: HashMap<(&str, i64, i64), Vec<String>> = HashMap::new();
+    for tier_id in main_tier_ids.iter() {
+        for a in eaf.get_tier(tier_id).iter().flat_map(|t| t.annotations.iter()) {
+            if let (Some(t1), Some(t2)) = a.ts_val() {
+                spans.entry((tier_id.as_str(), t1, t2)).or_insert(Vec::new()).push(a.id());
+            }
+        }
+    }
+    spans.values_mut().for_each(|ids| ids.reverse());
+
+    // Main tier annotation ID for each row, matched on tier ID and time span.
+    // Rows with identical spans are matched in order.
+    let mut main_ids: Vec<String> = Vec::new();
+    for (_, t1, t2, tier_id) in main_annotations.iter() {
+        match spans.get_mut(&(tier_id.as_str(), *t1, *t2)).and_then(|ids| ids.pop()) {
+            Some(id) => main_ids.push(id),
+            None => {
+                let msg = format!("(!) Failed to match row {t1} - {t2} ms to an annotation in tier '{tier_id}'.");
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+            }
+        }
+    }
+
+    // Continue annotation ID numbering after existing annotations
+    let mut index = eaf.tiers.iter()
+        .flat_map(|t| t.annotations.iter())
+        .filter_map(|a| a.id().trim_start_matches('a').parse::<usize>().ok())
+        .max()
+        .unwrap_or(0);
+
+    // Suffix ref tier IDs with main tier ID if there are multiple main tiers
+    let ref_tier_id = |column: &str, main_tier_id: &str| -> String {
+        match main_tier_ids.len() {
+            1 => column.to_owned(),
+            _ => format!("{column}@{main_tier_id}")
+        }
+    };
+
+    // Ref annotation ID for each (ref column, row)
+    let mut ref_ids: HashMap<(String, usize), String> = HashMap::new();
+    let mut orphans: HashMap<String, usize> = HashMap::new();
+
+    for ref_column in ordered_ref_columns(ref_columns)?.iter() {
+        for main_tier_id in main_tier_ids.iter() {
+            let tier_id = ref_tier_id(&ref_column.column, main_tier_id);
+            let parent_tier_id = match &ref_column.parent {
+                Some(p) => ref_tier_id(p, main_tier_id),
+                None => main_tier_id.to_owned()
+            };
+            if eaf.get_tier(&tier_id).is_some() {
+                let msg = format!("(!) Referred tier ID '{tier_id}' already exists.");
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+            }
+
+            let mut annotations: Vec<Annotation> = Vec::new();
+            for (row, (_, _, _, id)) in main_annotations.iter().enumerate() {
+                if id != main_tier_id {
+                    continue
+                }
+                let value = match ref_rows[row].get(&ref_column.column) {
+                    Some(v) if !v.is_empty() => v,
+                    _ => continue
+                };
+                let parent_id = match &ref_column.parent {
+                    Some(p) => match ref_ids.get(&(p.to_owned(), row)) {
+                        Some(a_id) => a_id.to_owned(),
+                        None => {
+                            *orphans.entry(ref_column.column.to_owned()).or_insert(0) += 1;
+                            continue
+                        }
+                    },
+                    None => main_ids[row].to_owned()
+                };
+                index += 1;
+                // Symbolic associations have no time values of their own
+                let annotation = AnnotationBuilder::new()
+                    .annotation_id(format!("a{index}"))
+                    .annotation_ref(parent_id)
+                    .annotation_value(value)
+                    .build()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                ref_ids.insert((ref_column.column.to_owned(), row), annotation.id());
+                annotations.push(annotation);
+            }
+
+            // Skip empty tiers, nested tiers then have no annotations either.
+            // Parent tiers are added first, since columns are ordered on parent.
+            if !annotations.is_empty() {
+                let tier = Tier::new(
+                    &tier_id,
+                    Some(&annotations),
+                    Some(REF_LINGUISTIC_TYPE),
+                    Some(&parent_tier_id)
+                );
+                eaf.add_tier(Some(tier), Some(&StereoType::SymbolicAssociation))?;
+            }
+        }
+    }
+
+    for (column, count) in orphans.iter() {
+        println!("(!) Skipped {count} values in '{column}' with empty parent cell.");
+    }
+
+    Ok(())
+}
+
 /// csv2eaf main
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     // CSV-file path
@@ -96,74 +434,140 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     }
 
     // default value "0"
-    // currently unimplemented
-    let _offset: i64 = match args.get_one::<String>("offset").unwrap().parse() {
-        Ok(num) => num,
-        Err(err) => {
-            let msg = format!("(!) 'offset' must be an integer: {err}");
+    let offset_string = args.get_one::<String>("offset").unwrap(); // clap default "0"
+    let offset: i64 = match string2offset(offset_string) {
+        Some(num) => num,
+        None => {
+            let msg = format!("(!) 'offset' must be milliseconds or [-]HH:MM:SS.fff, not '{offset_string}'.");
             return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
         }
     };
+    // Column with per-row offset, added to 'offset'
+    let offset_col = args.get_one::<String>("offset-column");
+    let clamp = *args.get_one::<bool>("clamp").unwrap();
+    // Sort rows and drop invalid rows instead of raising an error
+    let fix = *args.get_one::<bool>("fix").unwrap();
+
+    // Spreadsheet, e.g. xlsx, ods
+    let from_spreadsheet = is_spreadsheet(csv_path);
+    let sheet = args.get_one::<String>("sheet");
+
+    if from_spreadsheet && rttm_mode {
+        let msg = format!("(!) RTTM mode can not be used with spreadsheets.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
 
-    // Read csv file
-    let reader_builder = csv::ReaderBuilder::new()
-        .has_headers(has_headers)
-        .trim(Trim::All)
-        .delimiter(delimiter)
-        // .double_quote(false)
-        // .escape(Some(b'\\'))
-        // .flexible(true)
-        // .comment(Some(b'#'))
-        .from_path(csv_path);
-
-    let mut reader = match reader_builder {
-        Ok(rdr) => rdr,
-        Err(err) => {
-            let msg = format!("(!) Error parsing '{}': {err}", csv_path.display());
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
-        }
-    };
+    // Column headers, and rows with line numbers
+    let (headers, records) = match from_spreadsheet {
+        true => {
+            let (headers, records) = spreadsheet::read(csv_path, sheet.map(|s| s.as_str()))?;
+            if debug {
+                println!("-- SHEET START --");
+                println!("[HDR] LEN: {:3} | {headers:?}", headers.len());
+                for (line, cells) in records.iter() {
+                    println!("[{line:>5}] LEN: {:3} | {cells:?}", cells.len());
+                }
+                println!("--- SHEET END ---");
+                println!("Media:     {media:?}");
 
-    if debug {
-        println!("-- FILE START --");
-        match reader.headers() {
-            Ok(hdr) => println!("[HDR] LEN: {:3} | {hdr:?}", hdr.len()),
-            Err(err) => println!("[HDR] FAILED TO READ HEADERS: {err}")
-        }
-        for result in reader.records() {
-            match result {
-                Ok(record) => println!("[REC] LEN: {:3} | {record:?}", record.len()),
-                Err(err) => println!("[REC] FAILED TO READ RECORD: {err}")
+                return Ok(())
+            }
+            (headers, records)
+        },
+        false => {
+            if sheet.is_some() {
+                println!("(!) '{}' is not a spreadsheet. Ignoring '--sheet'.", csv_path.display());
+            }
+
+            // Read csv file
+            let reader_builder = csv::ReaderBuilder::new()
+                .has_headers(has_headers)
+                .trim(Trim::All)
+                .delimiter(delimiter)
+                // .double_quote(false)
+                // .escape(Some(b'\\'))
+                // .flexible(true)
+                // .comment(Some(b'#'))
+                .from_path(csv_path);
+
+            let mut reader = match reader_builder {
+                Ok(rdr) => rdr,
+                Err(err) => {
+                    let msg = format!("(!) Error parsing '{}': {err}", csv_path.display());
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+                }
+            };
+
+            if debug {
+                println!("-- FILE START --");
+                match reader.headers() {
+                    Ok(hdr) => println!("[HDR] LEN: {:3} | {hdr:?}", hdr.len()),
+                    Err(err) => println!("[HDR] FAILED TO READ HEADERS: {err}")
+                }
+                for result in reader.records() {
+                    match result {
+                        Ok(record) => println!("[REC] LEN: {:3} | {record:?}", record.len()),
+                        Err(err) => println!("[REC] FAILED TO READ RECORD: {err}")
+                    }
+                }
+                println!("--- FILE END ---");
+                println!("Delimiter: {delimiter_string}");
+                println!("Headers:   {has_headers}");
+                println!("RTTM mode: {rttm_mode}");
+                println!("Media:     {media:?}");
+
+                return Ok(())
+            }
+
+            // RTTM-files are parsed separately below
+            match rttm_mode {
+                true => (Vec::new(), Vec::new()),
+                false => read_csv(&mut reader)?
             }
         }
-        println!("--- FILE END ---");
-        println!("Delimiter: {delimiter_string}");
-        println!("Headers:   {has_headers}");
-        println!("RTTM mode: {rttm_mode}");
-        println!("Media:     {media:?}");
-        
-        return Ok(())
-    }
+    };
 
-    // Annotations in main tier
-    // (annotation value, time value ms start, time value ms end, ref annotation value)
-    let mut main_annotations: Vec<(String, i64, i64, String)> = Vec::new();
-    // Annotations in referred tier, if specified.
-    // Must equal in length to 'annotations'
-    // Will use the annotation ID as value with same vec index
-    // in 'annotations'.
-    // Hashmap: key: (ref_tier ID, parent tier ID) value: ref_tier annotation values
-    let mut ref_annotations: HashMap<(String, String), Vec<String>> = HashMap::new();
+    // Rows in file order
+    let mut rows: Vec<Row> = Vec::new();
+    // (line number, issue)
+    let mut issues: Vec<(usize, Issue)> = Vec::new();
+    // Rows discarded by '--clamp'
+    let mut discarded = 0;
     let mut ref_col_missing: HashSet<String> = HashSet::new();
+    // Columns with values for referred tiers
+    let ref_columns: Vec<RefColumn> = args.get_many::<String>("ref-values")
+        .map(|v| v.map(|s| RefColumn::from_str(s)).collect())
+        .unwrap_or_default();
+
+    if rttm_mode && !ref_columns.is_empty() {
+        let msg = format!("(!) '--refs' can not be used in RTTM mode.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
 
     // Parse as RTTM file
     if rttm_mode {
         let rttm = Rttm::read(csv_path, false)?;
-        rttm.iter()
-            .for_each(|seg| {
-                let (t1, t2) = seg.timespan_ms();
-                main_annotations.push((String::from(""), t1, t2, seg.speaker_name.to_owned()))
-            });
+        for (i, seg) in rttm.iter().enumerate() {
+            let (t1, t2) = seg.timespan_ms();
+            let timespan = match apply_offset(t1, t2, offset, clamp) {
+                Ok(Some(t)) => Some(t),
+                Ok(None) => {
+                    discarded += 1;
+                    continue
+                },
+                Err(issue) => {
+                    issues.push((i + 1, issue));
+                    None
+                }
+            };
+            rows.push(Row {
+                line: i + 1,
+                value: String::from(""),
+                tier_id: seg.speaker_name.to_owned(),
+                timespan,
+                refs: HashMap::new(),
+            })
+        }
 
     // Parse as CSV file with headers
     } else {
@@ -174,94 +578,128 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         let values_col = args.get_one::<String>("values").unwrap(); // clap ensures value
         // column with tier ID if multiple main tiers in one sheet
         let tier_id_col = args.get_one::<String>("tier-id");
-        // column headers for values that go into referred tiers, one for each ref col
-        let ref_values: Vec<String> = args.get_many::<String>("ref-values").unwrap_or_default().cloned().collect();
-        if let Some(_) = args.get_many::<String>("ref-values") {
-            println!("(!) Ref values will be ignored for now");
-        }
 
-        // If columns with referred annotation values are specified,
-        // column with tier ID must also be specified (since this will be the parent tier)
-        if !ref_values.is_empty() && tier_id_col.is_none() {
-            let msg = format!("Tier ID column not specifified, required for referred annotaions.");
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        // Rows can not be validated without the required columns
+        let missing: Vec<(usize, Issue)> = [Some(start_col), Some(end_col), Some(values_col), tier_id_col, offset_col]
+            .iter()
+            .flatten()
+            .filter(|col| !headers.iter().any(|h| h.as_str() == col.as_str()))
+            .map(|col| (1, Issue::MissingColumn(col.to_string())))
+            .collect();
+        if !missing.is_empty() {
+            print_issues(&missing, csv_path);
+            let msg = format!("(!) Missing columns in '{}'. Use '--debug' to inspect headers.", csv_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
         }
-    
-        // hashmap example from
-        // https://docs.rs/csv/latest/csv/tutorial/index.html#delimiters-quotes-and-variable-length-records
-        // 'Record' derived via serde support: each row becomes a hashmap if parse successful.
-        type HeaderRecord = HashMap<String, String>;
-        for result in reader.deserialize() {
-            
-            let record: HeaderRecord = result?; // one row
-            
-            let value = match record.get(values_col) {
-                Some(val) => val,
-                None => {
-                    let msg = format!("(!) No column named '{values_col}'");
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
-                }
+
+        for (line, cells) in records.into_iter() {
+            // Each row becomes a hashmap, key: column header
+            let record: HashMap<&str, Cell> = headers.iter()
+                .map(|h| h.as_str())
+                .zip(cells)
+                .collect();
+
+            // Columns are checked above
+            let value = record.get(values_col.as_str()).map(|c| c.text()).unwrap_or_default();
+            let tier_id = match tier_id_col {
+                Some(id) => record.get(id.as_str()).map(|c| c.text()).unwrap_or_default(),
+                None => String::from("default")
             };
-            let t1 =  match record.get(start_col) {
-                Some(t) => {
-                    if let Some(num) = string2ms(t) {
-                        num
-                    } else {
-                        let msg = format!("(!) Start time: Failed to convert '{t}' to milliseconds.");
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
-                    }
-                },
-                None => {
-                    let msg = format!("(!) No column named '{start_col}'");
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+
+            // Log all invalid time stamps in row
+            let mut parse_time = |column: &str, label: &str, offset: bool| -> Option<i64> {
+                let t = match record.get(column) {
+                    // Native spreadsheet time cell
+                    Some(Cell::Ms(ms)) => return Some(*ms),
+                    Some(Cell::Text(t)) => t.as_str(),
+                    None => ""
+                };
+                let parsed = match offset {
+                    true if t.is_empty() => Some(0),
+                    true => string2offset(t),
+                    false => string2ms(t)
+                };
+                if parsed.is_none() {
+                    issues.push((line, Issue::InvalidTime { column: label.to_owned(), value: t.to_owned() }));
                 }
+                parsed
             };
-            let t2 =  match record.get(end_col) {
-                Some(t) => {
-                    if let Some(num) = string2ms(t) {
-                        num
-                    } else {
-                        let msg = format!("(!) End time: Failed to convert '{t}' to milliseconds.");
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
-                    }
-                },
-                None => {
-                    let msg = format!("(!) No column named '{end_col}'");
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
-                }
+            let t1 = parse_time(start_col, "Start time", false);
+            let t2 = parse_time(end_col, "End time", false);
+            let row_offset = match offset_col {
+                Some(col) => parse_time(col, "Offset", true),
+                None => Some(0)
             };
-            let tier_id = match tier_id_col {
-                Some(id) => match record.get(id) {
-                    Some(val) => val.to_owned(),
-                    None => {
-                        let msg = format!("(!) No column named '{end_col}'");
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+
+            let timespan = match (t1, t2, row_offset) {
+                (Some(t1), Some(t2), Some(o)) => match apply_offset(t1, t2, offset + o, clamp) {
+                    Ok(Some(t)) => Some(t),
+                    Ok(None) => {
+                        discarded += 1;
+                        continue
+                    },
+                    Err(issue) => {
+                        issues.push((line, issue));
+                        None
                     }
                 },
-                None => String::from("default")
+                _ => None
             };
-    
-            println!("{t1:>10} - {t2:<10} | {} ", value.to_owned());
-    
-            main_annotations.push((value.to_owned(), t1, t2, tier_id.to_owned()));
-    
-            // Since ref value correspond to a single row, it is always known which the parent tier is
-            // build ref tiers content
-            for ref_col in ref_values.iter() {
+
+            // Since ref values correspond to a single row, the parent annotation is always known
+            let mut refs: HashMap<String, String> = HashMap::new();
+            for ref_column in ref_columns.iter() {
+                let ref_col = &ref_column.column;
                 if let Some(ref_annotation) = record.get(ref_col.as_str()) {
-                    ref_annotations.entry((ref_col.to_owned(), tier_id.to_owned()))
-                        .or_insert(Vec::new())
-                        .push(ref_annotation.to_owned());
-                } else {
+                    refs.insert(ref_col.to_owned(), ref_annotation.text());
+                } else if ref_col_missing.insert(ref_col.to_owned()) {
                     println!("(!) No column named '{ref_col}'. Ignoring.");
-                    ref_col_missing.insert(ref_col.to_owned());
                 }
             }
+
+            rows.push(Row { line, value, tier_id, timespan, refs });
         }
     }
 
+    let total = rows.len();
+    let rows = validate(rows, &mut issues);
 
+    if !issues.is_empty() {
+        print_issues(&issues, csv_path);
+        if !fix {
+            let clamp_hint = match issues.iter().any(|(_, i)| matches!(i, Issue::Negative { .. })) {
+                true => " Use '--clamp' to clamp negative start times to 0 ms.",
+                false => ""
+            };
+            let msg = format!("(!) Found {} problems in '{}'. Use '--fix' to sort rows and drop invalid rows.{clamp_hint}",
+                issues.len(),
+                csv_path.display()
+            );
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        }
+        let sorted = issues.iter().filter(|(_, i)| !i.drops_row()).count();
+        println!("Fixed: Sorted {sorted} out-of-order rows, dropped {} of {total} rows.", total - rows.len());
+    }
+    if discarded > 0 {
+        println!("Discarded {discarded} rows ending at or before 0 ms after offset.");
+    }
+    if rows.is_empty() {
+        let msg = format!("(!) No valid rows in '{}'", csv_path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
 
+    // Annotations in main tier
+    // (annotation value, time value ms start, time value ms end, tier ID)
+    let mut main_annotations: Vec<(String, i64, i64, String)> = Vec::new();
+    // Values for referred tiers, if specified, one hashmap per row in 'main_annotations'.
+    // Hashmap: key: ref column, value: ref annotation value
+    let mut ref_rows: Vec<HashMap<String, String>> = Vec::new();
+    for row in rows.into_iter() {
+        let (t1, t2) = row.timespan.unwrap_or_default(); // validated rows have time stamps
+        println!("{t1:>10} - {t2:<10} | {} ", row.value);
+        main_annotations.push((row.value, t1, t2, row.tier_id));
+        ref_rows.push(row.refs);
+    }
 
     // // Map optional video path to vec
     // let media_paths = match media {
@@ -270,32 +708,17 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     // };
 
     let mut eaf = match Eaf::from_values_multi(&main_annotations) {
-        Ok(mut e) => {
-            let main_a_count = e.a_len();
-            let mut ref_count = 0;
-            if !ref_annotations.is_empty() {
-                let lt_type_ref = "ref-tier-symbolic-association";
-                let st = StereoType::SymbolicAssociation;
-                // let lt = LinguisticType::new(lt_type_ref, Some(&st));
-                // e.add_linguistic_type(&lt, true);
-                
-                for ((ref_id, parent_id), annotations) in ref_annotations.iter() {
-                    if let Some(parent) = e.get_tier(&parent_id) {
-                        if let Ok(t) = Tier::ref_from_values(&annotations, ref_id, parent, lt_type_ref, Some(main_a_count + ref_count)) {
-                            ref_count += t.len();
-                            e.add_tier(Some(t), Some(&st))?;
-                        }
-                    }
-                }
-            }
-            e
-        },
+        Ok(e) => e,
         Err(err) => {
             let msg = format!("(!) Failed to generate ELAN-file: {err}");
             return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
         }
     };
 
+    if !ref_columns.is_empty() {
+        add_ref_tiers(&mut eaf, &main_annotations, &ref_rows, &ref_columns)?;
+    }
+
     // Link media files
     eaf.with_media_mut(&media);
 
@@ -311,7 +734,10 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         }
     }
 
-    let eaf_path = csv_path.with_extension("eaf");
+    let eaf_path = match args.get_one::<PathBuf>("out") {
+        Some(p) => create_parent(p)?,
+        None => csv_path.with_extension("eaf")
+    };
     let eaf_string = match eaf.to_string(Some(4)) {
         Ok(s) => s,
         Err(err) => {
@@ -321,8 +747,8 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     };
     // Not using the Eaf::write() method, as it does not return a Result<bool, EafError>
     match writefile(eaf_string.as_bytes(), &eaf_path) {
-        Ok(true) => println!("Wrote {}", eaf_path.display()),
-        Ok(false) => println!("User aborted writing ELAN-file"),
+        Ok(Some(p)) => println!("Wrote {}", p.display()),
+        Ok(None) => println!("Skipped writing {}", eaf_path.display()),
         Err(err) => {
             let msg = format!("(!) Failed to write '{}': {err}", eaf_path.display());
             return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
@@ -0,0 +1,458 @@
+//! Diarization error rate (DER) for speaker segments, e.g. the output
+//! of a speaker diarization system, relative to reference speaker segments.
+//! Reference and hypothesis can each be either an RTTM-file,
+//! or an ELAN-file with one tier per speaker.
+//!
+//! DER is the sum of missed speech, false alarm, and speaker confusion,
+//! relative to the total reference speech time, where:
+//! - Missed speech: reference speakers without a hypothesis speaker.
+//! - False alarm: hypothesis speakers without a reference speaker.
+//! - Speaker confusion: reference speakers assigned to the wrong
+//!   hypothesis speaker.
+//!
+//! Speaker names need not match. Reference and hypothesis speakers are mapped
+//! one-to-one, so that the total time they overlap is maximised.
+//! Time within the collar of any reference segment boundary is not scored.
+//!
+//! RTTM-files may contain several recordings (file IDs). Each recording is
+//! scored separately, with its own speaker mapping, and the totals are pooled.
+//! If reference and hypothesis contain a single recording each,
+//! these are scored against each other regardless of file ID.
+//! For ELAN-files, the file name is used as file ID.
+
+use std::{collections::{BTreeMap, BTreeSet}, path::{Path, PathBuf}};
+
+use eaf_rs::eaf::{Eaf, Tier};
+use rttm_rs::Rttm;
+
+use crate::{
+    compare::agreement::segments,
+    eaf::find_tiers,
+    files::{file_stem_as_string, has_extension},
+};
+
+/// Speaker segment.
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub speaker: String,
+    pub start: i64,
+    pub end: i64,
+}
+
+/// Speaker turns per file ID.
+pub type FileTurns = BTreeMap<String, Vec<Turn>>;
+
+/// Reads speaker turns from an RTTM-file, grouped by file ID.
+fn read_rttm(path: &Path) -> std::io::Result<FileTurns> {
+    let rttm = Rttm::read(path, false)?;
+    let mut turns = FileTurns::new();
+    for seg in rttm.iter() {
+        let (start, end) = seg.timespan_ms();
+        turns.entry(seg.file_id.to_owned()).or_default().push(Turn {
+            speaker: seg.speaker_name.to_owned(),
+            start,
+            end
+        });
+    }
+    Ok(turns)
+}
+
+/// Reads speaker turns from an ELAN-file, using the tier ID as speaker.
+/// Uses tiers matching `tier_selectors` if set, otherwise all main tiers.
+fn read_eaf(path: &Path, tier_selectors: &[&str]) -> std::io::Result<FileTurns> {
+    let eaf = match Eaf::read(path) {
+        Ok(f) => f,
+        Err(err) => {
+            let msg = format!("(!) Error parsing '{}': {err}", path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    let tiers: Vec<Tier> = match tier_selectors.is_empty() {
+        true => eaf.tiers.iter()
+            .filter(|t| t.parent_ref.is_none())
+            .cloned()
+            .collect(),
+        false => find_tiers(&eaf, tier_selectors)?
+    };
+
+    let turns: Vec<Turn> = tiers.iter()
+        .flat_map(|tier| segments(&eaf, tier).into_iter()
+            .map(|s| Turn {
+                speaker: tier.tier_id.to_owned(),
+                start: s.start,
+                end: s.end
+            })
+        )
+        .collect();
+
+    let mut file_turns = FileTurns::new();
+    if !turns.is_empty() {
+        file_turns.insert(file_stem_as_string(path).unwrap_or_default(), turns);
+    }
+    Ok(file_turns)
+}
+
+/// Reads speaker turns from an RTTM-file or an ELAN-file,
+/// depending on file extension, grouped by file ID.
+pub fn read_turns(path: &Path, tier_selectors: &[&str]) -> std::io::Result<FileTurns> {
+    let turns = match has_extension(path, "rttm") {
+        true => read_rttm(path)?,
+        false => read_eaf(path, tier_selectors)?
+    };
+    if turns.values().all(|t| t.is_empty()) {
+        let msg = format!("(!) No speaker segments in '{}'", path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
+    Ok(turns)
+}
+
+/// Diarization error, times in milliseconds.
+#[derive(Debug, Clone, Default)]
+pub struct DiarizationError {
+    pub collar_ms: i64,
+    /// Total reference speech time, scored regions only.
+    /// Overlapping speech counts once per speaker.
+    pub reference_ms: i64,
+    pub missed_ms: i64,
+    pub false_alarm_ms: i64,
+    pub confusion_ms: i64,
+    /// `(reference speaker, hypothesis speaker, overlap)`
+    pub mapping: Vec<(String, String, i64)>,
+    pub unmapped_reference: Vec<String>,
+    pub unmapped_hypothesis: Vec<String>,
+}
+
+impl DiarizationError {
+    pub fn new(reference: &[Turn], hypothesis: &[Turn], collar_ms: i64) -> Self {
+        let speakers_ref: Vec<String> = reference.iter().map(|t| t.speaker.to_owned()).collect::<BTreeSet<_>>().into_iter().collect();
+        let speakers_hyp: Vec<String> = hypothesis.iter().map(|t| t.speaker.to_owned()).collect::<BTreeSet<_>>().into_iter().collect();
+
+        // No-score zones around reference boundaries, sorted and merged
+        let mut collars: Vec<(i64, i64)> = reference.iter()
+            .flat_map(|t| [t.start, t.end])
+            .map(|b| (b - collar_ms, b + collar_ms))
+            .collect();
+        collars.sort();
+        let mut no_score: Vec<(i64, i64)> = Vec::new();
+        for (start, end) in collars.into_iter() {
+            match no_score.last_mut() {
+                Some((_, prev_end)) if start <= *prev_end => *prev_end = (*prev_end).max(end),
+                _ => no_score.push((start, end))
+            }
+        }
+
+        // Elementary intervals, within which the set of active speakers is constant
+        let mut bounds: Vec<i64> = reference.iter().chain(hypothesis.iter())
+            .flat_map(|t| [t.start, t.end])
+            .chain(no_score.iter().flat_map(|(s, e)| [*s, *e]))
+            .collect();
+        bounds.sort();
+        bounds.dedup();
+
+        // Sweep intervals in order, tracking active speakers via sorted turn boundaries
+        let ref_events = events(reference, &speakers_ref);
+        let hyp_events = events(hypothesis, &speakers_hyp);
+        let mut ref_active = vec![0usize; speakers_ref.len()];
+        let mut hyp_active = vec![0usize; speakers_hyp.len()];
+        let (mut ref_i, mut hyp_i, mut no_score_i) = (0, 0, 0);
+
+        // (duration, active reference speakers, active hypothesis speakers)
+        let mut intervals: Vec<(i64, Vec<usize>, Vec<usize>)> = Vec::new();
+        for w in bounds.windows(2) {
+            let (t1, t2) = (w[0], w[1]);
+            advance(&ref_events, &mut ref_i, &mut ref_active, t1);
+            advance(&hyp_events, &mut hyp_i, &mut hyp_active, t1);
+
+            // No-score zone bounds are interval bounds, so an interval is either within or outside a zone
+            while no_score.get(no_score_i).map(|(_, e)| *e <= t1).unwrap_or(false) {
+                no_score_i += 1;
+            }
+            if no_score.get(no_score_i).map(|(s, _)| *s <= t1).unwrap_or(false) {
+                continue
+            }
+
+            let r = active_speakers(&ref_active);
+            let h = active_speakers(&hyp_active);
+            if !r.is_empty() || !h.is_empty() {
+                intervals.push((t2 - t1, r, h));
+            }
+        }
+
+        // Overlap per speaker pair, in scored regions
+        let mut overlap = vec![vec![0i64; speakers_hyp.len()]; speakers_ref.len()];
+        for (dur, r, h) in intervals.iter() {
+            for i in r.iter() {
+                for j in h.iter() {
+                    overlap[*i][*j] += dur;
+                }
+            }
+        }
+
+        let assignment = assign(&overlap);
+
+        let mut der = Self {
+            collar_ms,
+            ..Self::default()
+        };
+        for (dur, r, h) in intervals.iter() {
+            let correct = r.iter()
+                .filter(|i| assignment[**i].map(|j| h.contains(&j)).unwrap_or(false))
+                .count() as i64;
+            let (n_ref, n_hyp) = (r.len() as i64, h.len() as i64);
+            der.reference_ms += dur * n_ref;
+            der.missed_ms += dur * (n_ref - n_hyp).max(0);
+            der.false_alarm_ms += dur * (n_hyp - n_ref).max(0);
+            der.confusion_ms += dur * (n_ref.min(n_hyp) - correct);
+        }
+
+        for (i, speaker) in speakers_ref.iter().enumerate() {
+            match assignment[i] {
+                Some(j) => der.mapping.push((speaker.to_owned(), speakers_hyp[j].to_owned(), overlap[i][j])),
+                None => der.unmapped_reference.push(speaker.to_owned())
+            }
+        }
+        der.unmapped_hypothesis = speakers_hyp.iter().enumerate()
+            .filter(|(j, _)| !assignment.contains(&Some(*j)))
+            .map(|(_, s)| s.to_owned())
+            .collect();
+
+        der
+    }
+
+    /// Total error in milliseconds.
+    pub fn error_ms(&self) -> i64 {
+        self.missed_ms + self.false_alarm_ms + self.confusion_ms
+    }
+
+    /// Returns `value` relative to reference speech time,
+    /// or `None` if there is no scored reference speech.
+    fn rate(&self, value: i64) -> Option<f64> {
+        match self.reference_ms {
+            0 => None,
+            n => Some(value as f64 / n as f64)
+        }
+    }
+
+    pub fn der(&self) -> Option<f64> {
+        self.rate(self.error_ms())
+    }
+
+    /// Pools scores for several recordings.
+    /// Speaker mappings are per recording and are not kept.
+    pub fn pool(scores: &[Self], collar_ms: i64) -> Self {
+        let mut der = Self {
+            collar_ms,
+            ..Self::default()
+        };
+        for score in scores.iter() {
+            der.reference_ms += score.reference_ms;
+            der.missed_ms += score.missed_ms;
+            der.false_alarm_ms += score.false_alarm_ms;
+            der.confusion_ms += score.confusion_ms;
+        }
+        der
+    }
+
+    pub fn print(&self) {
+        self.print_mapping();
+        self.print_error();
+    }
+
+    pub fn print_mapping(&self) {
+        println!("[ Speaker mapping ]");
+        for (r, h, o) in self.mapping.iter() {
+            println!("  {r:<20} -> {h:<20} ({o} ms overlap)");
+        }
+        for r in self.unmapped_reference.iter() {
+            println!("  {r:<20} -> (none)");
+        }
+        for h in self.unmapped_hypothesis.iter() {
+            println!("  {:<20} -> {h} (not in reference)", "(none)");
+        }
+    }
+
+    pub fn print_error(&self) {
+        let opt = |v: Option<f64>| v.map(|n| format!("{:.2}%", n * 100.)).unwrap_or("NA".to_owned());
+        println!("[ Diarization error ] Collar: +/- {} ms", self.collar_ms);
+        println!("  Reference speech     | {} ms", self.reference_ms);
+        println!("  Missed speech        | {:>10} ms {:>8}", self.missed_ms, opt(self.rate(self.missed_ms)));
+        println!("  False alarm          | {:>10} ms {:>8}", self.false_alarm_ms, opt(self.rate(self.false_alarm_ms)));
+        println!("  Speaker confusion    | {:>10} ms {:>8}", self.confusion_ms, opt(self.rate(self.confusion_ms)));
+        println!("  DER                  | {:>10} ms {:>8}", self.error_ms(), opt(self.der()));
+    }
+}
+
+/// Returns turn boundaries as `(time, speaker index, is start)`,
+/// sorted on time. Turns without duration are ignored.
+/// `speakers` must be sorted.
+fn events(turns: &[Turn], speakers: &[String]) -> Vec<(i64, usize, bool)> {
+    let mut events: Vec<(i64, usize, bool)> = turns.iter()
+        .filter(|t| t.end > t.start)
+        .filter_map(|t| speakers.binary_search(&t.speaker).ok()
+            .map(|i| [(t.start, i, true), (t.end, i, false)])
+        )
+        .flatten()
+        .collect();
+    events.sort();
+    events
+}
+
+/// Applies all events at or before `time`, starting at `index`,
+/// to the number of active turns per speaker.
+fn advance(events: &[(i64, usize, bool)], index: &mut usize, active: &mut [usize], time: i64) {
+    while let Some((t, speaker, is_start)) = events.get(*index) {
+        if *t > time {
+            break
+        }
+        match is_start {
+            true => active[*speaker] += 1,
+            false => active[*speaker] -= 1,
+        }
+        *index += 1;
+    }
+}
+
+/// Returns indices for speakers with at least one active turn.
+fn active_speakers(active: &[usize]) -> Vec<usize> {
+    active.iter().enumerate()
+        .filter(|(_, n)| **n > 0)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Maximum weight one-to-one assignment of rows to columns in `weights`
+/// (Hungarian algorithm). Returns the assigned column for each row, if any.
+/// Pairs with zero weight are left unassigned.
+fn assign(weights: &[Vec<i64>]) -> Vec<Option<usize>> {
+    let rows = weights.len();
+    let cols = weights.first().map(|r| r.len()).unwrap_or(0);
+    let n = rows.max(cols);
+    if n == 0 {
+        return vec![None; rows]
+    }
+
+    // Square cost matrix, minimising negated weights, padded with zeros
+    let cost = |i: usize, j: usize| -> i64 {
+        -weights.get(i).and_then(|r| r.get(j)).copied().unwrap_or(0)
+    };
+
+    // Potentials and matching are 1-indexed, 0 is a sentinel
+    let mut u = vec![0i64; n + 1];
+    let mut v = vec![0i64; n + 1];
+    let mut p = vec![0usize; n + 1]; // p[j]: row matched to column j
+    let mut way = vec![0usize; n + 1];
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = i64::MAX;
+            let mut j1 = 0;
+            for j in 1..=n {
+                if !used[j] {
+                    let cur = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                    if cur < minv[j] {
+                        minv[j] = cur;
+                        way[j] = j0;
+                    }
+                    if minv[j] < delta {
+                        delta = minv[j];
+                        j1 = j;
+                    }
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if p[j0] == 0 {
+                break
+            }
+        }
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break
+            }
+        }
+    }
+
+    let mut assignment = vec![None; rows];
+    for j in 1..=n {
+        let i = p[j];
+        if i > 0 && i <= rows && j <= cols && weights[i-1][j-1] > 0 {
+            assignment[i-1] = Some(j-1);
+        }
+    }
+    assignment
+}
+
+pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
+    let reference_path = args.get_one::<PathBuf>("reference").unwrap(); // clap ensures value
+    let hypothesis_path = args.get_one::<PathBuf>("hypothesis").unwrap(); // clap ensures value
+    let collar_ms = *args.get_one::<i64>("collar").unwrap(); // clap default 250
+    let tier_selectors: Vec<&str> = args.get_many::<String>("tier")
+        .map(|v| v.map(|s| s.as_str()).collect())
+        .unwrap_or_default();
+
+    if collar_ms < 0 {
+        let msg = format!("(!) 'collar' must be zero or a positive integer.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
+
+    let reference = read_turns(reference_path, &tier_selectors)?;
+    let hypothesis = read_turns(hypothesis_path, &tier_selectors)?;
+
+    println!("Reference:  {}", reference_path.display());
+    println!("Hypothesis: {}", hypothesis_path.display());
+
+    // Single recordings are scored against each other regardless of file ID,
+    // e.g. an ELAN-file and RTTM-output for the same recording
+    if reference.len() == 1 && hypothesis.len() == 1 {
+        let (reference, hypothesis) = (reference.values().next(), hypothesis.values().next());
+        DiarizationError::new(
+            reference.map(|t| t.as_slice()).unwrap_or_default(),
+            hypothesis.map(|t| t.as_slice()).unwrap_or_default(),
+            collar_ms
+        ).print();
+        return Ok(())
+    }
+
+    // Score each recording separately, with its own speaker mapping.
+    // Recordings missing in the hypothesis are scored as missed speech,
+    // and vice versa as false alarm.
+    let file_ids: BTreeSet<&String> = reference.keys().chain(hypothesis.keys()).collect();
+    let mut scores: Vec<DiarizationError> = Vec::new();
+    for file_id in file_ids.into_iter() {
+        println!("[{file_id}]");
+        if !reference.contains_key(file_id) {
+            println!("  (!) Not in reference");
+        }
+        if !hypothesis.contains_key(file_id) {
+            println!("  (!) Not in hypothesis");
+        }
+        let score = DiarizationError::new(
+            reference.get(file_id).map(|t| t.as_slice()).unwrap_or_default(),
+            hypothesis.get(file_id).map(|t| t.as_slice()).unwrap_or_default(),
+            collar_ms
+        );
+        score.print();
+        scores.push(score);
+    }
+
+    println!("[ Total: {} recordings ]", scores.len());
+    DiarizationError::pool(&scores, collar_ms).print_error();
+
+    Ok(())
+}
@@ -1,10 +1,11 @@
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 
 use eaf_rs::eaf::{
     Eaf,
     Tier,
     Annotation
 };
+use regex::Regex;
 
 use crate::text::process_string;
 
@@ -93,4 +94,128 @@ pub fn select_annotation(tier: &Tier) -> std::io::Result<Annotation> {
             Err(_) => println!("(!) Not a number.")
         }
     }
-}
\ No newline at end of file
+}
+
+/// Returns `true` if the user may be prompted for input,
+/// i.e. if explicitly requested or stdin is a terminal.
+pub fn can_prompt(interactive: bool) -> bool {
+    interactive || std::io::stdin().is_terminal()
+}
+
+/// Returns the tier with ID `selector`, or the single tier with an ID
+/// fully matching `selector` as a regular expression.
+pub fn find_tier(eaf: &Eaf, selector: &str, no_tokenized: bool) -> std::io::Result<Tier> {
+    let tier = match eaf.get_tier(selector) {
+        Some(t) => t.to_owned(),
+        None => {
+            let rx = Regex::new(&format!("^(?:{selector})$")).ok();
+            let matches: Vec<&Tier> = eaf.tiers.iter()
+                .filter(|t| rx.as_ref().map(|r| r.is_match(&t.tier_id)).unwrap_or(false))
+                .collect();
+            match matches.len() {
+                1 => matches[0].to_owned(),
+                0 => {
+                    let msg = format!("(!) No tier matching '{selector}'. Available tiers: {}", eaf.tier_ids().join(", "));
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                },
+                _ => {
+                    let ids: Vec<&str> = matches.iter().map(|t| t.tier_id.as_str()).collect();
+                    let msg = format!("(!) Multiple tiers matching '{selector}': {}", ids.join(", "));
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+            }
+        }
+    };
+
+    if no_tokenized && eaf.is_tokenized(&tier.tier_id, true)? {
+        let msg = format!("(!) '{}' or one of its parents is tokenized.", tier.tier_id);
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
+
+    Ok(tier)
+}
+
+/// Returns the annotation in `tier` with annotation ID `selector` (e.g. 'a12'),
+/// or at the 1-based index `selector` (the list number in the Grid tab in ELAN).
+pub fn find_annotation(tier: &Tier, selector: &str) -> std::io::Result<Annotation> {
+    if let Some(a) = tier.annotations.iter().find(|a| a.id() == selector) {
+        return Ok(a.to_owned())
+    }
+
+    match selector.parse::<usize>().ok().and_then(|i| tier.annotations.get(i.checked_sub(1)?)) {
+        Some(a) => Ok(a.to_owned()),
+        None => {
+            let msg = format!("(!) No annotation with ID or index '{selector}' in '{}' ({} annotations).", tier.tier_id, tier.len());
+            Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    }
+}
+
+/// Resolves a tier via `selector` if set, otherwise prompts the user
+/// if allowed (see `can_prompt()`).
+pub fn resolve_tier(eaf: &Eaf, selector: Option<&str>, interactive: bool, no_tokenized: bool) -> std::io::Result<Tier> {
+    match selector.filter(|s| !s.is_empty()) {
+        Some(sel) => find_tier(eaf, sel, no_tokenized),
+        None => {
+            if can_prompt(interactive) {
+                select_tier(eaf, no_tokenized)
+            } else {
+                let msg = format!("(!) No tier specified. Use '--tier' with one of: {}", eaf.tier_ids().join(", "));
+                Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        }
+    }
+}
+
+/// Resolves an annotation in `tier` via `selector` if set, otherwise prompts the user
+/// if allowed (see `can_prompt()`).
+pub fn resolve_annotation(tier: &Tier, selector: Option<&str>, interactive: bool) -> std::io::Result<Annotation> {
+    match selector.filter(|s| !s.is_empty()) {
+        Some(sel) => find_annotation(tier, sel),
+        None => {
+            if can_prompt(interactive) {
+                select_annotation(tier)
+            } else {
+                let msg = format!("(!) No annotation specified. Use '--annotation' with an annotation ID or index (1-{}) in '{}'.", tier.len(), tier.tier_id);
+                Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        }
+    }
+}
+
+/// Returns all tiers with an ID equal to, or fully matching as a regular expression,
+/// any of `selectors`. Fails if a selector matches no tier.
+pub fn find_tiers(eaf: &Eaf, selectors: &[&str]) -> std::io::Result<Vec<Tier>> {
+    let mut tiers: Vec<Tier> = Vec::new();
+    for selector in selectors.iter() {
+        let rx = Regex::new(&format!("^(?:{selector})$")).ok();
+        let matches: Vec<&Tier> = eaf.tiers.iter()
+            .filter(|t| t.tier_id == *selector || rx.as_ref().map(|r| r.is_match(&t.tier_id)).unwrap_or(false))
+            .collect();
+        if matches.is_empty() {
+            let msg = format!("(!) No tier matching '{selector}'. Available tiers: {}", eaf.tier_ids().join(", "));
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+        for tier in matches.into_iter() {
+            if !tiers.iter().any(|t| t.tier_id == tier.tier_id) {
+                tiers.push(tier.to_owned())
+            }
+        }
+    }
+    Ok(tiers)
+}
+
+/// Returns the time span in milliseconds for the annotation with ID `annotation_id`
+/// in tier `tier_id`. Referred annotations without time values use the
+/// time span of the main annotation they refer to.
+pub fn annotation_timespan(eaf: &Eaf, tier_id: &str, annotation_id: &str) -> (Option<i64>, Option<i64>) {
+    let ts_val = eaf.get_tier(tier_id)
+        .and_then(|t| t.annotations.iter().find(|a| a.id() == annotation_id))
+        .map(|a| a.ts_val());
+    match ts_val {
+        Some((Some(t1), Some(t2))) => (Some(t1), Some(t2)),
+        _ => eaf.main_annotation(annotation_id)
+            .map(|a| a.ts_val())
+            .unwrap_or((None, None))
+    }
+}
@@ -1,84 +1,290 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::Path};
 
 use csv;
-use eaf_rs::{Annotation, Eaf};
+use eaf_rs::eaf::{Annotation, Eaf, Tier};
+use inquire::MultiSelect;
 use time::{Time, ext::NumericalDuration};
-// use inquire::{self, validator::Validation, list_option::ListOption, formatter::MultiOptionFormatter, MultiSelect};
-
-use crate::files::{select_tiers, writefile};
-
-// const TIER_ATTRIBUTES: [&'static str; 5] = [
-//     "Tier ID",
-//     "Tier linguistic type",
-//     "Tier annotator",
-//     "Tier participant",
-//     "Tier parent ID",
-// ];
-// const ANNOTATION_ATTRIBUTES: [&'static str; 7] = [
-//     "Annotation ID",
-//     "Annotation value",
-//     "Annotation timespan as HH:MM:SS.fff",
-//     "Annotation timespan as milliseconds",
-//     "Annotation duration",
-//     "Annotation parent ID",
-//     "Annotation time slot reference",
-// ];
-
-// fn create_menu<'a>(options: &[&'a str], min_choices: Option<usize>, max_choices: Option<usize>) {
-//     let min = min_choices.unwrap_or(0);
-//     let max = max_choices.unwrap_or(0);
-
-//     let validator = |a: &[ListOption<&&str>]| {
-//         if a.len() < min && min != 0 {
-//             return Ok(Validation::Invalid(format!("At least {min} values must be selected.").into()));
-//         }
-
-//         if a.len() > max && max != 0 {
-//             return Ok(Validation::Invalid(format!("At most {min} values can be selected.").into()));
-//         }
-
-//         Ok(Validation::Valid)
-
-//         // let x = a.iter().any(|o| *o.value == "Pineapple");
-
-//         // match x {
-//         //     true => Ok(Validation::Valid),
-//         //     false => Ok(Validation::Invalid("Remember to buy pineapples".into())),
-//         // }
-//     };
-
-//     // fn validator2(a: &[ListOption<&&str>], min: usize, max: usize) -> Validation {
-//     //     if a.len() < min && min != 0 {
-//     //         return Validation::Invalid(format!("At least {min} values must be selected.").into());
-//     //     }
-
-//     //     if a.len() > max && max != 0 {
-//     //         return Validation::Invalid(format!("At most {min} values can be selected.").into());
-//     //     }
-
-//     //     Validation::Valid
-//     // }
-
-//     let formatter: MultiOptionFormatter<'_, &str> = &|a| format!("{} options", a.len());
-
-//     let options = options.iter().cloned().collect_vec();
-
-//     let ans = MultiSelect::new("Select which attributes to include:", options)
-//         .with_validator(validator)
-//         .with_formatter(formatter)
-//         .prompt();
-
-//     match ans {
-//         Ok(_) => println!("I'll get right on it"),
-//         Err(_) => println!("The shopping list could not be processed"),
-//     }
-
-// }
+
+use crate::{
+    eaf::{can_prompt, find_tiers},
+    files::{select_tiers, writefile, Batch, ErrorReport},
+};
+
+/// Exported CSV column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    File,
+    TierId,
+    TierSize,
+    TierParent,
+    TierType,
+    TierParticipant,
+    TierAnnotator,
+    TierLanguage,
+    AnnotationId,
+    AnnotationValue,
+    StartHms,
+    StartMs,
+    EndHms,
+    EndMs,
+    DurationHms,
+    DurationMs,
+    TimeSlotRef1,
+    TimeSlotRef2,
+    ParentAnnotationId,
+    ParentAnnotationValue,
+    CveRef,
+}
+
+impl Column {
+    /// Columns exported if none are specified.
+    pub const DEFAULT: [Column; 11] = [
+        Self::TierId,
+        Self::TierSize,
+        Self::TierParent,
+        Self::TierType,
+        Self::TierParticipant,
+        Self::TierAnnotator,
+        Self::AnnotationValue,
+        Self::StartHms,
+        Self::StartMs,
+        Self::EndHms,
+        Self::EndMs,
+    ];
+
+    /// Include options for `--include`, long form,
+    /// with a description for interactive selection.
+    pub const OPTIONS: [(&'static str, &'static str); 17] = [
+        ("file", "File name"),
+        ("tier-id", "Tier ID"),
+        ("tier-type", "Tier linguistic type"),
+        ("tier-length", "Tier size (number of annotations)"),
+        ("tier-parent", "Tier parent ID"),
+        ("tier-annotator", "Tier annotator"),
+        ("tier-participant", "Tier participant"),
+        ("tier-language", "Tier language"),
+        ("annotation-id", "Annotation ID"),
+        ("annotation-value", "Annotation value"),
+        ("annotation-time-ms", "Annotation start, end, duration as milliseconds"),
+        ("annotation-time-hms", "Annotation start, end, duration as HH:MM:SS.fff"),
+        ("annotation-duration", "Annotation duration as milliseconds"),
+        ("annotation-time-slots", "Annotation time slot references"),
+        ("annotation-parent-id", "Parent annotation ID"),
+        ("annotation-parent-value", "Parent annotation value"),
+        ("annotation-cv-entry", "Controlled vocabulary entry ID"),
+    ];
+
+    /// Returns the columns for an `--include` value, short or long form.
+    pub fn from_include(include: &str) -> Vec<Self> {
+        match include {
+            "f" | "file" => vec![Self::File],
+            "ti" | "tier-id" => vec![Self::TierId],
+            "tt" | "tier-type" => vec![Self::TierType],
+            "tl" | "tier-length" => vec![Self::TierSize],
+            "tpnt" | "tier-parent" => vec![Self::TierParent],
+            "ta" | "tier-annotator" => vec![Self::TierAnnotator],
+            "tprt" | "tier-participant" => vec![Self::TierParticipant],
+            "tlng" | "tier-language" => vec![Self::TierLanguage],
+            "ai" | "annotation-id" => vec![Self::AnnotationId],
+            "av" | "annotation-value" => vec![Self::AnnotationValue],
+            "ams" | "annotation-time-ms" => vec![Self::StartMs, Self::EndMs, Self::DurationMs],
+            "ahms" | "annotation-time-hms" => vec![Self::StartHms, Self::EndHms, Self::DurationHms],
+            "ad" | "annotation-duration" => vec![Self::DurationMs],
+            "ats" | "annotation-time-slots" => vec![Self::TimeSlotRef1, Self::TimeSlotRef2],
+            "apid" | "annotation-parent-id" => vec![Self::ParentAnnotationId],
+            "apv" | "annotation-parent-value" => vec![Self::ParentAnnotationValue],
+            "acv" | "annotation-cv-entry" => vec![Self::CveRef],
+            _ => Vec::new()
+        }
+    }
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Self::File => "FILE",
+            Self::TierId => "TIER_ID",
+            Self::TierSize => "TIER_SIZE",
+            Self::TierParent => "PARENT_TIER",
+            Self::TierType => "TIER_TYPE",
+            Self::TierParticipant => "PARTICIPANTS",
+            Self::TierAnnotator => "ANNOTATOR",
+            Self::TierLanguage => "TIER_LANGUAGE",
+            Self::AnnotationId => "ANNOTATION_ID",
+            Self::AnnotationValue => "ANNOTATION_VALUE",
+            Self::StartHms => "ANNOTATION_START_HHMMSS",
+            Self::StartMs => "ANNOTATION_START_MS",
+            Self::EndHms => "ANNOTATION_END_HHMMSS",
+            Self::EndMs => "ANNOTATION_END_MS",
+            Self::DurationHms => "ANNOTATION_DURATION_HHMMSS",
+            Self::DurationMs => "ANNOTATION_DURATION_MS",
+            Self::TimeSlotRef1 => "TIME_SLOT_REF1",
+            Self::TimeSlotRef2 => "TIME_SLOT_REF2",
+            Self::ParentAnnotationId => "PARENT_ANNOTATION_ID",
+            Self::ParentAnnotationValue => "PARENT_ANNOTATION_VALUE",
+            Self::CveRef => "CV_ENTRY_ID",
+        }
+    }
+
+    /// Returns the value for this column in `row`.
+    fn value(&self, row: &Row) -> String {
+        let none = || "<NONE>".to_owned();
+        let (start, end) = row.timespan;
+        let duration = match row.timespan {
+            (Some(t1), Some(t2)) => Some(t2 - t1),
+            _ => None
+        };
+        let tier = row.tier;
+        let annotation = row.annotation;
+        match self {
+            Self::File => row.file_name.to_owned(),
+            Self::TierId => tier.tier_id.to_owned(),
+            Self::TierSize => tier.len().to_string(),
+            Self::TierParent => tier.parent_ref.to_owned().unwrap_or_else(none),
+            Self::TierType => tier.linguistic_type_ref.to_owned(),
+            Self::TierParticipant => tier.participant.to_owned().unwrap_or_else(none),
+            Self::TierAnnotator => tier.annotator.to_owned().unwrap_or_else(none),
+            Self::TierLanguage => tier.lang_ref.to_owned().unwrap_or_else(none),
+            Self::AnnotationId => annotation.map(|a| a.id()).unwrap_or_default(),
+            Self::AnnotationValue => annotation.map(|a| a.value().to_string()).unwrap_or("<EMPTY TIER>".to_owned()),
+            Self::StartHms => start.map(ms2string).unwrap_or_default(),
+            Self::StartMs => start.map(|t| t.to_string()).unwrap_or_default(),
+            Self::EndHms => end.map(ms2string).unwrap_or_default(),
+            Self::EndMs => end.map(|t| t.to_string()).unwrap_or_default(),
+            Self::DurationHms => duration.map(ms2string).unwrap_or_default(),
+            Self::DurationMs => duration.map(|d| d.to_string()).unwrap_or_default(),
+            Self::TimeSlotRef1 => annotation.and_then(|a| a.ts_ref().0).unwrap_or_default(),
+            Self::TimeSlotRef2 => annotation.and_then(|a| a.ts_ref().1).unwrap_or_default(),
+            Self::ParentAnnotationId => row.parent.map(|p| p.id()).unwrap_or_default(),
+            Self::ParentAnnotationValue => row.parent.map(|p| p.value().to_string()).unwrap_or_default(),
+            Self::CveRef => annotation.and_then(|a| a.cve_ref()).unwrap_or_default(),
+        }
+    }
+}
+
+/// Values shared by all columns in a row.
+struct Row<'a> {
+    file_name: &'a str,
+    tier: &'a Tier,
+    /// `None` for empty tiers.
+    annotation: Option<&'a Annotation>,
+    timespan: (Option<i64>, Option<i64>),
+    parent: Option<&'a Annotation>,
+}
+
+/// Annotation lookup for an ELAN-file, built once per file.
+struct AnnotationIndex<'a> {
+    /// Key: annotation ID.
+    annotations: HashMap<String, &'a Annotation>,
+    /// Key: annotation ID, value: parent annotation ID.
+    parents: HashMap<String, String>,
+}
+
+impl<'a> AnnotationIndex<'a> {
+    fn new(eaf: &'a Eaf) -> Self {
+        let mut index = Self {
+            annotations: HashMap::new(),
+            parents: HashMap::new(),
+        };
+        for tier in eaf.tiers.iter() {
+            for a in tier.annotations.iter() {
+                index.annotations.insert(a.id(), a);
+                if let Some(ref_id) = a.ref_id() {
+                    index.parents.insert(a.id(), ref_id);
+                }
+            }
+        }
+
+        // Time-aligned referred tiers have no annotation references.
+        // The parent is the annotation in the parent tier containing the start time.
+        for tier in eaf.tiers.iter() {
+            let parent_tier = match tier.parent_ref.as_deref().and_then(|p| eaf.get_tier(p)) {
+                Some(t) => t,
+                None => continue
+            };
+            // (start, end, annotation ID), sorted on start
+            let mut spans: Vec<(i64, i64, String)> = parent_tier.annotations.iter()
+                .filter_map(|a| match index.timespan(&a.id()) {
+                    (Some(t1), Some(t2)) => Some((t1, t2, a.id())),
+                    _ => None
+                })
+                .collect();
+            spans.sort();
+            for a in tier.annotations.iter().filter(|a| a.ref_id().is_none()) {
+                let start = match a.ts_val().0 {
+                    Some(t) => t,
+                    None => continue
+                };
+                let i = spans.partition_point(|(t1, _, _)| *t1 <= start);
+                if let Some((_, t2, id)) = i.checked_sub(1).map(|i| &spans[i]) {
+                    if start < *t2 {
+                        index.parents.insert(a.id(), id.to_owned());
+                    }
+                }
+            }
+        }
+
+        index
+    }
+
+    fn parent(&self, annotation_id: &str) -> Option<&'a Annotation> {
+        self.parents.get(annotation_id)
+            .and_then(|id| self.annotations.get(id))
+            .copied()
+    }
+
+    /// Returns the time span of the annotation, or of its closest
+    /// ancestor with both time values set.
+    fn timespan(&self, annotation_id: &str) -> (Option<i64>, Option<i64>) {
+        let mut id = annotation_id;
+        // Limit depth in case of circular references
+        for _ in 0..self.annotations.len() {
+            if let Some((Some(t1), Some(t2))) = self.annotations.get(id).map(|a| a.ts_val()) {
+                return (Some(t1), Some(t2))
+            }
+            match self.parents.get(id) {
+                Some(p) => id = p,
+                None => break
+            }
+        }
+        (None, None)
+    }
+
+    /// Returns the ID of the main annotation the annotation belongs to,
+    /// following parent annotations up to the main tier.
+    fn root_id(&self, annotation_id: &str) -> String {
+        let mut id = annotation_id;
+        for _ in 0..self.annotations.len() {
+            match self.parents.get(id) {
+                Some(p) => id = p,
+                None => break
+            }
+        }
+        id.to_owned()
+    }
+}
+
+/// Prompts the user for columns to export.
+fn select_columns() -> std::io::Result<Vec<Column>> {
+    let options: Vec<&str> = Column::OPTIONS.iter().map(|(_, d)| *d).collect();
+    let selected = match MultiSelect::new("Select columns to include:", options).prompt() {
+        Ok(s) => s,
+        Err(err) => {
+            let msg = format!("(!) Failed to select columns: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        }
+    };
+    Ok(Column::OPTIONS.iter()
+        .filter(|(_, d)| selected.contains(d))
+        .flat_map(|(o, _)| Column::from_include(o))
+        .collect())
+}
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
 
-    let eaf_path = args.get_one::<PathBuf>("eaf").unwrap(); // clap required arg
-    // let interactive = *args.get_one::<bool>("interactive").unwrap();
+    let batch = Batch::from_args(args)?;
+    let interactive = *args.get_one::<bool>("interactive").unwrap();
+    let tier_selectors: Vec<&str> = args.get_many::<String>("tier")
+        .map(|v| v.map(|s| s.as_str()).collect())
+        .unwrap_or_default();
 
     let delimiter_string = args.get_one::<String>("delimiter").cloned().unwrap_or_default(); // clap ensures value
     let delimiter = match delimiter_string.as_str() { // clap ensures value, deafult to tab
@@ -91,8 +297,63 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         }
     };
     let timeline = *args.get_one::<bool>("timeline").unwrap();
+    let wide = *args.get_one::<bool>("wide").unwrap();
+
+    // Columns in specified order, duplicates removed
+    let mut columns: Vec<Column> = Vec::new();
+    let includes: Vec<&String> = args.get_many::<String>("include")
+        .map(|v| v.collect())
+        .unwrap_or_default();
+    for column in includes.iter().flat_map(|i| Column::from_include(i)) {
+        if !columns.contains(&column) {
+            columns.push(column)
+        }
+    }
+    if columns.is_empty() && !timeline {
+        columns = match interactive {
+            true if can_prompt(interactive) => select_columns()?,
+            true => {
+                let msg = format!("(!) Can not prompt for columns. Use '--include' to select columns.");
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+            },
+            false => Column::DEFAULT.to_vec()
+        };
+    }
+    if columns.is_empty() && !timeline {
+        let msg = format!("(!) No columns selected.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
 
-    let csv_path = eaf_path.with_extension("csv");
+    let mut errors = ErrorReport::new();
+    for eaf_path in batch.paths.iter() {
+        if let Err(err) = eaf2csv(eaf_path, delimiter, timeline, &columns, wide, &tier_selectors, interactive, &batch) {
+            if !batch.is_dir() {
+                return Err(err)
+            }
+            println!("(!) Failed to export '{}': {err}", eaf_path.display());
+            errors.add(eaf_path, &err);
+        }
+    }
+
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
+
+    Ok(())
+}
+
+/// Exports a single ELAN-file as CSV.
+fn eaf2csv(
+    eaf_path: &Path,
+    delimiter: u8,
+    timeline: bool,
+    columns: &[Column],
+    wide: bool,
+    tier_selectors: &[&str],
+    interactive: bool,
+    batch: &Batch
+) -> std::io::Result<()> {
+    let csv_path = batch.outpath(&eaf_path.with_extension("csv"))?;
 
     let eaf = Eaf::read(eaf_path)?;
 
@@ -106,43 +367,46 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     // .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
 
     if timeline {
-        let tier_ids = select_tiers(&eaf).unwrap();
-        let tiers: Vec<_> = tier_ids.iter()
-            .filter_map(|id| eaf.get_tier(id))
-            .cloned()
-            .collect();
-        
-        if tier_ids.len() != tiers.len() {
-            let tier_ids_out: Vec<&str> = tiers.iter()
-                .map(|t| t.tier_id.as_str())
-                .collect();
-            let missing: Vec<_> = tier_ids.iter()
-                .filter(|id| !tier_ids_out.contains(&id.as_str()))
-                .map(|s| s.as_str())
+        let tiers = if !tier_selectors.is_empty() {
+            find_tiers(&eaf, tier_selectors)?
+        } else if can_prompt(interactive) {
+            let tier_ids = match select_tiers(&eaf) {
+                Ok(ids) => ids,
+                Err(err) => {
+                    let msg = format!("(!) Failed to select tiers: {err}");
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+                }
+            };
+            let tiers: Vec<_> = tier_ids.iter()
+                .filter_map(|id| eaf.get_tier(id))
+                .cloned()
                 .collect();
-            let msg = format!("Failed to retreive tiers {}", missing.join(", "));
+
+            if tier_ids.len() != tiers.len() {
+                let tier_ids_out: Vec<&str> = tiers.iter()
+                    .map(|t| t.tier_id.as_str())
+                    .collect();
+                let missing: Vec<_> = tier_ids.iter()
+                    .filter(|id| !tier_ids_out.contains(&id.as_str()))
+                    .map(|s| s.as_str())
+                    .collect();
+                let msg = format!("Failed to retreive tiers {}", missing.join(", "));
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+            }
+            tiers
+        } else {
+            let msg = format!("(!) No tiers specified. Use '--tier' with one or more of: {}", eaf.tier_ids().join(", "));
             return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
-        }
+        };
 
-        let len = tiers.len() + 1; // one column per tier + timestamp column
+        let csv = timeline_csv(&eaf, &tiers, delimiter)?;
 
-        let mut annotations: Vec<(String, Annotation)> = tiers.iter()
-            .flat_map(|t| t.annotations.as_slice())
-            .map(|a| (a.tier_id().unwrap().to_owned(), a.to_owned()))
-            .collect();
-        annotations.sort_by_key(|(_, a)| a.ts_val().0.expect("Annotation level timestamps not set"));
-        for (id, a) in annotations.iter() {
-            let (ts1, ts2) = a.ts_val();
-            println!("{} {:10?} - {:10?} {}", id, ts1, ts2, a.value())
+        match writefile(csv.as_bytes(), &csv_path) {
+            Ok(Some(p)) => println!("Wrote {}", p.display()),
+            Ok(None) => println!("Skipped writing {}", csv_path.display()),
+            Err(e) => return Err(e)
         }
 
-        // for id in tier_ids.iter() {
-        //     let tier = eaf.get_tier(id).unwrap();
-        //     let a = tier.first().unwrap();
-        //     let (start, end) = a.ts_val();
-        //     println!("{} {} {} - {} ms", tier.tier_id, a.value(), start.unwrap(), end.unwrap());
-        // }
-
         return Ok(())
     }
     
@@ -151,61 +415,67 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         .has_headers(true)
         .from_writer(vec![]);
 
-    let headers = vec![
-        "TIER_ID",
-        "TIER_SIZE",
-        "PARENT_TIER",
-        "TIER_TYPE",
-        "PARTICIPANTS",
-        "ANNOTATOR",
-        "ANNOTATION_VALUE",
-        "ANNOTATION_START_HHMMSS",
-        "ANNOTATION_START_MS",
-        "ANNOTTION_END_HHMMSS",
-        "ANNOTTION_END_MS"
-    ];
+    // Wide layout: one row per main annotation, with a column per referred tier
+    let ref_tiers: Vec<&Tier> = match wide {
+        true => eaf.tiers.iter().filter(|t| t.parent_ref.is_some()).collect(),
+        false => Vec::new()
+    };
 
+    let mut headers: Vec<String> = columns.iter()
+        .map(|c| c.header().to_owned())
+        .collect();
+    headers.extend(ref_tiers.iter().map(|t| t.tier_id.to_owned()));
     builder.write_record(&headers)?;
 
+    let index = AnnotationIndex::new(&eaf);
+    let file_name = eaf_path.file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // Referred annotation values per main annotation ID, per referred tier
+    let ref_values: Vec<HashMap<String, Vec<String>>> = ref_tiers.iter()
+        .map(|t| {
+            let mut values: HashMap<String, Vec<String>> = HashMap::new();
+            for a in t.annotations.iter() {
+                values.entry(index.root_id(&a.id())).or_insert(Vec::new()).push(a.value().to_string());
+            }
+            values
+        })
+        .collect();
+
     for tier in eaf.tiers.iter() {
+        if wide && tier.parent_ref.is_some() {
+            continue
+        }
         if tier.is_empty() {
-            let row = vec![
-                tier.tier_id.to_owned(),
-                tier.len().to_string(),
-                tier.parent_ref.as_deref().unwrap_or("<NONE>").to_owned(),
-                tier.linguistic_type_ref.to_owned(),
-                tier.participant.as_deref().unwrap_or("<NONE>").to_owned(),
-                tier.annotator.as_deref().unwrap_or("<NONE>").to_owned(),
-                "<EMPTY TIER>".to_string(),
-                "".to_string(),
-                "".to_string(),
-                "".to_string(),
-                "".to_string(),
-            ];
+            let row = Row {
+                file_name: &file_name,
+                tier,
+                annotation: None,
+                timespan: (None, None),
+                parent: None,
+            };
+            let mut row: Vec<String> = columns.iter()
+                .map(|c| c.value(&row))
+                .collect();
+            row.extend(ref_tiers.iter().map(|_| String::new()));
             builder.write_record(row)?;
         }
         for annotation in tier.annotations.iter() {
-            let (ts1, ts2) = annotation.ts_val();
-            // let midnight = Time::MIDNIGHT;
-            // let t_hms1 = (midnight + ts1.unwrap_or_default().milliseconds()).as_hms_milli();
-            // let t_hms2 = (midnight + ts2.unwrap_or_default().milliseconds()).as_hms_milli();
-            let t_hms1 = ms2string(ts1.unwrap_or_default());
-            let t_hms2 = ms2string(ts2.unwrap_or_default());
-            let row = vec![
-                tier.tier_id.to_owned(),
-                tier.len().to_string(),
-                tier.parent_ref.as_deref().unwrap_or("<NONE>").to_owned(),
-                tier.linguistic_type_ref.to_owned(),
-                tier.participant.as_deref().unwrap_or("<NONE>").to_owned(),
-                tier.annotator.as_deref().unwrap_or("<NONE>").to_owned(),
-                annotation.value().to_string(),
-                // format!("{:02}:{:02}:{:02}.{:03}", t_hms1.0, t_hms1.1, t_hms1.2, t_hms1.3),
-                t_hms1,
-                ts1.unwrap_or_default().to_string(),
-                // format!("{:02}:{:02}:{:02}.{:03}", t_hms2.0, t_hms2.1, t_hms2.2, t_hms2.3),
-                t_hms2,
-                ts2.unwrap_or_default().to_string(),
-            ];
+            let id = annotation.id();
+            let row = Row {
+                file_name: &file_name,
+                tier,
+                annotation: Some(annotation),
+                timespan: index.timespan(&id),
+                parent: index.parent(&id),
+            };
+            let mut row: Vec<String> = columns.iter()
+                .map(|c| c.value(&row))
+                .collect();
+            row.extend(ref_values.iter()
+                .map(|v| v.get(&id).map(|v| v.join(" ")).unwrap_or_default())
+            );
             builder.write_record(row)?;
         }
     }
@@ -214,8 +484,8 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     let tsv = String::from_utf8(builder_inner).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
 
     match writefile(tsv.as_bytes(), &csv_path) {
-        Ok(true) => println!("Wrote {}", csv_path.display()),
-        Ok(false) => println!("User aborted writing file"),
+        Ok(Some(p)) => println!("Wrote {}", p.display()),
+        Ok(None) => println!("Skipped writing {}", csv_path.display()),
         Err(e) => return Err(e)
     }
 
@@ -224,7 +494,81 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     Ok(())
 }
 
-fn ms2string(ms: i64) -> String {
+/// Generates a time-aligned CSV with one column per tier in `tiers`,
+/// and one row per time interval, where intervals are delimited by the union
+/// of all annotation boundaries in `tiers`. Each tier column contains the value
+/// of the annotation covering the interval, if any. Intervals where no tier
+/// has an annotation are left out. Annotations without time values use the
+/// time span of their closest ancestor annotation with time values,
+/// and are otherwise ignored.
+fn timeline_csv(eaf: &Eaf, tiers: &[Tier], delimiter: u8) -> std::io::Result<String> {
+    let index = AnnotationIndex::new(eaf);
+
+    // (start, end, value) per tier, sorted on start time
+    let tier_spans: Vec<Vec<(i64, i64, String)>> = tiers.iter()
+        .map(|t| {
+            let mut spans: Vec<(i64, i64, String)> = t.annotations.iter()
+                .filter_map(|a| match index.timespan(&a.id()) {
+                    (Some(t1), Some(t2)) => Some((t1, t2, a.to_str().to_owned())),
+                    _ => None
+                })
+                .collect();
+            spans.sort_by_key(|(t1, t2, _)| (*t1, *t2));
+            spans
+        })
+        .collect();
+
+    let mut boundaries: Vec<i64> = tier_spans.iter()
+        .flat_map(|spans| spans.iter().flat_map(|(t1, t2, _)| [*t1, *t2]))
+        .collect();
+    boundaries.sort();
+    boundaries.dedup();
+
+    let mut builder = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(vec![]);
+
+    let mut headers = vec![
+        "START_HHMMSS".to_owned(),
+        "START_MS".to_owned(),
+        "END_HHMMSS".to_owned(),
+        "END_MS".to_owned(),
+        "DURATION_MS".to_owned(),
+    ];
+    headers.extend(tiers.iter().map(|t| t.tier_id.to_owned()));
+    builder.write_record(&headers)?;
+
+    for w in boundaries.windows(2) {
+        let (start, end) = (w[0], w[1]);
+        // Overlapping annotations within a tier are joined
+        let values: Vec<String> = tier_spans.iter()
+            .map(|spans| spans.iter()
+                .filter(|(t1, t2, _)| *t1 <= start && end <= *t2)
+                .map(|(_, _, v)| v.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+            )
+            .collect();
+        if values.iter().all(|v| v.is_empty()) {
+            continue
+        }
+        let mut row = vec![
+            ms2string(start),
+            start.to_string(),
+            ms2string(end),
+            end.to_string(),
+            (end - start).to_string(),
+        ];
+        row.extend(values);
+        builder.write_record(&row)?;
+    }
+
+    let builder_inner = builder.into_inner().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    String::from_utf8(builder_inner).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+/// Formats milliseconds as `HH:MM:SS.fff`.
+pub fn ms2string(ms: i64) -> String {
     hms_milli_to_string(hms_milli(ms))
 }
 
@@ -0,0 +1,176 @@
+//! Converts speaker tiers in an ELAN-file to a Rich Transcription Time Marked
+//! (RTTM) file, e.g. to export a corrected speaker diarization.
+//! Each annotation becomes a `SPEAKER` line:
+//!
+//! ```text
+//! SPEAKER <file ID> <channel> <onset> <duration> <NA> <NA> <speaker> <NA> <NA>
+//! ```
+//!
+//! Onset and duration are in seconds. Speaker names are either tier IDs
+//! or tier participants, and the file ID is either the ELAN-file stem,
+//! or the file stem of the first linked media file.
+//!
+//! Tier IDs with a channel suffix `_ch<N>`, as generated by `rttm2eaf`
+//! for multi-channel files, are exported on channel `N`, with the suffix
+//! removed from the speaker name. Other tiers use the channel set with
+//! `--channel`, default `1`.
+
+use std::path::PathBuf;
+
+use eaf_rs::eaf::{Eaf, Tier};
+use rttm_rs::{Rttm, RttmSegment};
+
+use crate::{
+    compare::agreement::segments,
+    der::Turn,
+    eaf::find_tiers,
+    files::{create_parent, file_stem_as_string, writefile},
+};
+
+/// Splits a channel suffix `_ch<N>` from `tier_id`.
+/// Returns the tier ID without the suffix, and the channel, if any.
+fn split_channel(tier_id: &str) -> (&str, Option<usize>) {
+    match tier_id.rsplit_once("_ch") {
+        Some((id, ch)) if !id.is_empty() => match ch.parse::<usize>() {
+            Ok(n) => (id, Some(n)),
+            Err(_) => (tier_id, None)
+        },
+        _ => (tier_id, None)
+    }
+}
+
+/// Returns `(channel, speaker turn)` for annotations in `tier`, sorted on start time.
+/// Uses participant as speaker name if `participant` is `true`
+/// and participant is set, otherwise tier ID without channel suffix.
+/// Uses the channel in the tier ID suffix if set, otherwise `channel`.
+fn turns(eaf: &Eaf, tier: &Tier, participant: bool, channel: usize) -> Vec<(usize, Turn)> {
+    let (tier_id, suffix_channel) = split_channel(&tier.tier_id);
+    let speaker = match participant {
+        true => tier.participant.as_deref()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .unwrap_or(tier_id),
+        false => tier_id,
+    };
+    let channel = suffix_channel.unwrap_or(channel);
+    segments(eaf, tier).into_iter()
+        .map(|s| (channel, Turn {
+            speaker: speaker.to_owned(),
+            start: s.start,
+            end: s.end
+        }))
+        .collect()
+}
+
+/// Merges turns by the same speaker on the same channel separated by a gap
+/// of at most `max_gap_ms`, including overlapping turns.
+/// `turns` must be sorted on channel, speaker, then start time.
+fn merge(turns: Vec<(usize, Turn)>, max_gap_ms: i64) -> Vec<(usize, Turn)> {
+    let mut merged: Vec<(usize, Turn)> = Vec::new();
+    for (channel, turn) in turns.into_iter() {
+        match merged.last_mut() {
+            Some((prev_channel, prev)) if *prev_channel == channel
+                && prev.speaker == turn.speaker
+                && turn.start - prev.end <= max_gap_ms => {
+                prev.end = prev.end.max(turn.end);
+            },
+            _ => merged.push((channel, turn))
+        }
+    }
+    merged
+}
+
+/// Returns an RTTM `SPEAKER` segment.
+fn speaker_segment(file_id: &str, channel: usize, turn: &Turn) -> RttmSegment {
+    // RTTM fields are whitespace delimited
+    let speaker = turn.speaker.split_whitespace().collect::<Vec<_>>().join("_");
+    RttmSegment::speaker(file_id, channel, turn.start, turn.end - turn.start, &speaker)
+}
+
+/// eaf2rttm main
+pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
+    let eaf_path = args.get_one::<PathBuf>("eaf").unwrap(); // clap ensures value
+    let tier_selectors: Vec<&str> = args.get_many::<String>("tier")
+        .map(|v| v.map(|s| s.as_str()).collect())
+        .unwrap_or_default();
+    let participant = args.get_one::<String>("speaker").map(|s| s == "participant").unwrap_or(false); // clap default 'id'
+    let media_file_id = args.get_one::<String>("file-id").map(|s| s == "media").unwrap_or(false); // clap default 'stem'
+    let merge_gap = args.get_one::<i64>("merge");
+    let min_duration = *args.get_one::<i64>("min-duration").unwrap(); // clap default 0
+    let channel = *args.get_one::<usize>("channel").unwrap(); // clap default 1
+
+    let eaf = match Eaf::read(eaf_path) {
+        Ok(f) => f,
+        Err(err) => {
+            let msg = format!("(!) Failed to parse '{}': {err}", eaf_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    let tiers: Vec<Tier> = match tier_selectors.is_empty() {
+        true => eaf.tiers.iter()
+            .filter(|t| t.parent_ref.is_none())
+            .cloned()
+            .collect(),
+        false => find_tiers(&eaf, &tier_selectors)?
+    };
+
+    let file_id = match media_file_id {
+        true => match eaf.media_paths().first().and_then(|(abs, _)| file_stem_as_string(abs)) {
+            Some(stem) => stem,
+            None => {
+                let msg = format!("(!) No linked media in '{}' to derive file ID from.", eaf_path.display());
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        },
+        false => file_stem_as_string(eaf_path).unwrap_or_default()
+    };
+
+    let mut all_turns: Vec<(usize, Turn)> = tiers.iter()
+        .flat_map(|t| turns(&eaf, t, participant, channel))
+        .collect();
+
+    // Merge per speaker and channel, since multiple tiers may share a participant
+    all_turns.sort_by(|(c1, a), (c2, b)| c1.cmp(c2)
+        .then(a.speaker.cmp(&b.speaker))
+        .then(a.start.cmp(&b.start))
+    );
+    if let Some(gap) = merge_gap {
+        all_turns = merge(all_turns, *gap);
+    }
+    let total = all_turns.len();
+    all_turns.retain(|(_, t)| t.end - t.start >= min_duration);
+    let dropped = total - all_turns.len();
+
+    all_turns.sort_by_key(|(c, t)| (t.start, t.end, *c));
+
+    let rttm = Rttm::from_segments(
+        &all_turns.iter()
+            .map(|(c, t)| speaker_segment(&file_id, *c, t))
+            .collect::<Vec<_>>()
+    );
+
+    println!("Converted the following tiers:");
+    for (i, tier) in tiers.iter().enumerate() {
+        println!("{}. {} ({} annotations)", i+1, tier.tier_id, tier.len());
+    }
+    if dropped > 0 {
+        println!("Dropped {dropped} segments shorter than {min_duration} ms.");
+    }
+    println!("{} segments for file ID '{file_id}'.", all_turns.len());
+
+    let rttm_path = match args.get_one::<PathBuf>("out") {
+        Some(p) => create_parent(p)?,
+        None => eaf_path.with_extension("rttm")
+    };
+    match writefile(rttm.to_string().as_bytes(), &rttm_path) {
+        Ok(Some(p)) => println!("Wrote {}", p.display()),
+        Ok(None) => println!("Skipped writing {}", rttm_path.display()),
+        Err(err) => {
+            let msg = format!("(!) Failed to write '{}': {err}", rttm_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Generate ELAN template files (ETF) from ELAN-files.
+//! A template contains the tier hierarchy, linguistic types, constraints,
+//! controlled vocabularies, languages and locales, but no annotations,
+//! time slots, or linked media.
+//!
+//! Multiple templates can be merged into one. Tiers and definitions
+//! are added in file order. If a tier, linguistic type, controlled vocabulary,
+//! or language with the same ID is defined differently in a later file,
+//! the first definition is kept and the conflict is reported.
+
+use std::path::{Path, PathBuf};
+
+use eaf_rs::{Eaf, TimeOrder};
+
+/// Returns a copy of `eaf` with all annotations, time slots,
+/// and linked media removed.
+pub fn template(eaf: &Eaf) -> Eaf {
+    let mut etf = eaf.to_owned();
+    etf.tiers.iter_mut()
+        .for_each(|t| t.annotations.clear());
+    etf.time_order = TimeOrder { time_slots: Vec::new() };
+    etf.header.media_descriptor.clear();
+    etf.header.linked_file_descriptor.clear();
+    etf
+}
+
+/// Adds items in `other` to `items` if no item with the same key exists.
+/// If an item with the same key exists, but differs in definition,
+/// a conflict message is added to `conflicts`.
+fn merge_items<T: Clone + std::fmt::Debug>(
+    items: &mut Vec<T>,
+    other: &[T],
+    key: impl Fn(&T) -> String,
+    kind: &str,
+    source: &Path,
+    conflicts: &mut Vec<String>
+) {
+    for item in other.iter() {
+        match items.iter().find(|i| key(i) == key(item)) {
+            Some(existing) => {
+                // No PartialEq for all EAF structures, compare debug representations instead
+                if format!("{existing:?}") != format!("{item:?}") {
+                    conflicts.push(format!("{kind} '{}' in '{}' differs from first definition",
+                        key(item),
+                        source.display()
+                    ));
+                }
+            },
+            None => items.push(item.to_owned())
+        }
+    }
+}
+
+/// Merges the structures of multiple templates into one.
+/// The first template is used as base for header, properties etc.
+/// Returns the merged template, and a list of conflicting definitions.
+pub fn merge_templates(templates: &[(PathBuf, Eaf)]) -> Option<(Eaf, Vec<String>)> {
+    let (_, first) = templates.first()?;
+    let mut merged = first.to_owned();
+    let mut conflicts: Vec<String> = Vec::new();
+
+    for (path, etf) in templates.iter().skip(1) {
+        // Tiers are compared on linguistic type and parent only,
+        // since attributes such as annotator usually differ between files
+        for tier in etf.tiers.iter() {
+            match merged.tiers.iter().find(|t| t.tier_id == tier.tier_id) {
+                Some(existing) => {
+                    if existing.linguistic_type_ref != tier.linguistic_type_ref
+                        || existing.parent_ref != tier.parent_ref
+                    {
+                        conflicts.push(format!("Tier '{}' in '{}' has linguistic type '{}' and parent '{}', expected '{}' and '{}'",
+                            tier.tier_id,
+                            path.display(),
+                            tier.linguistic_type_ref,
+                            tier.parent_ref.as_deref().unwrap_or("None"),
+                            existing.linguistic_type_ref,
+                            existing.parent_ref.as_deref().unwrap_or("None"),
+                        ));
+                    }
+                },
+                None => merged.tiers.push(tier.to_owned())
+            }
+        }
+
+        merge_items(&mut merged.linguistic_types, &etf.linguistic_types,
+            |l| l.linguistic_type_id.to_owned(), "Linguistic type", path, &mut conflicts);
+        merge_items(&mut merged.constraints, &etf.constraints,
+            |c| c.stereotype.to_string(), "Constraint", path, &mut conflicts);
+        merge_items(&mut merged.controlled_vocabularies, &etf.controlled_vocabularies,
+            |cv| cv.cv_id.to_owned(), "Controlled vocabulary", path, &mut conflicts);
+        merge_items(&mut merged.languages, &etf.languages,
+            |l| l.lang_id.to_owned(), "Language", path, &mut conflicts);
+        merge_items(&mut merged.locales, &etf.locales,
+            |l| format!("{}-{}", l.language_code, l.country_code.as_deref().unwrap_or("")), "Locale", path, &mut conflicts);
+    }
+
+    Some((merged, conflicts))
+}
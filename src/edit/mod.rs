@@ -1,33 +1,252 @@
-use std::path::PathBuf;
+//! Edit tier attributes and structure for one or more ELAN-files.
+//! Specify a tier ID to only affect that tier, otherwise all tiers are edited.
+//!
+//! - Strip referred tiers (only those below the specified tier if set)
+//! - Set annotator, participant, and language attributes
+//! - Prefix and/or suffix tier IDs
+//! - Generate an ELAN template file (ETF), optionally merged from multiple ELAN-files
+//!
+//! The original ELAN-file is never overwritten. The result is written
+//! to a new file with `_edited` appended to the file name, or as an ETF
+//! with the same file name if `--etf` is set.
+
+use std::path::{Path, PathBuf};
 
 use eaf_rs::Eaf;
 
+use crate::files::{append_file_name, writefile, Batch, ErrorReport};
+
+pub mod etf;
+
+/// Tier edits to apply to each ELAN-file.
+#[derive(Debug, Default)]
+struct TierEdits<'a> {
+    /// Only edit this tier if set.
+    tier_id: Option<&'a str>,
+    strip_referred: bool,
+    annotator: Option<&'a str>,
+    participant: Option<&'a str>,
+    language: Option<&'a str>,
+    prefix: Option<&'a str>,
+    suffix: Option<&'a str>,
+}
+
+impl TierEdits<'_> {
+    fn is_empty(&self) -> bool {
+        !self.strip_referred
+            && self.annotator.is_none()
+            && self.participant.is_none()
+            && self.language.is_none()
+            && self.prefix.is_none()
+            && self.suffix.is_none()
+    }
+
+    /// Returns `true` if the tier should be edited.
+    fn includes(&self, tier_id: &str) -> bool {
+        self.tier_id.map(|id| id == tier_id).unwrap_or(true)
+    }
+}
+
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
-    let path = args.get_one::<PathBuf>("eaf"); // required unless 'dir'
-    let dir = args.get_one::<PathBuf>("dir"); // required unless ''af'
-    let tier_id = args.get_one::<String>("tier-id");
-    let strip_referred = *args.get_one::<bool>("strip-referred").unwrap();
-    let annotator = args.get_one::<String>("set-annotator");
-    let prefix = args.get_one::<String>("prefix");
-    let suffix = args.get_one::<String>("suffix");
+    let batch = Batch::from_args(args)?;
+    let edits = TierEdits {
+        tier_id: args.get_one::<String>("tier-id").map(|s| s.as_str()),
+        strip_referred: *args.get_one::<bool>("strip-referred").unwrap(),
+        annotator: args.get_one::<String>("set-annotator").map(|s| s.as_str()),
+        participant: args.get_one::<String>("set-participant").map(|s| s.as_str()),
+        language: args.get_one::<String>("set-language").map(|s| s.as_str()),
+        prefix: args.get_one::<String>("prefix").map(|s| s.as_str()),
+        suffix: args.get_one::<String>("suffix").map(|s| s.as_str()),
+    };
     let save_etf = *args.get_one::<bool>("etf").unwrap();
+    let merge_etf = *args.get_one::<bool>("merge-etf").unwrap(); // requires 'etf'
+
+    if edits.is_empty() && !save_etf {
+        let msg = format!("(!) No edits specified.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
+
+    // Templates to merge (file path, template)
+    let mut templates: Vec<(PathBuf, Eaf)> = Vec::new();
+
+    let mut errors = ErrorReport::new();
+    for path in batch.paths.iter() {
+        println!("[{}]", path.display());
+        let result = edit(path, &edits)
+            .and_then(|(eaf, mut summary)| {
+                if merge_etf {
+                    templates.push((path.to_owned(), etf::template(&eaf)));
+                } else if save_etf {
+                    let etf_path = batch.outpath(&path.with_extension("etf"))?;
+                    summary.push(write_eaf(&etf::template(&eaf), &etf_path)?);
+                } else {
+                    // Never overwrite the original ELAN-file
+                    let eaf_path = batch.outpath(&append_file_name(path, "edited"))?;
+                    summary.push(write_eaf(&eaf, &eaf_path)?);
+                }
+                Ok(summary)
+            });
+        match result {
+            Ok(summary) => {
+                for line in summary.iter() {
+                    println!("  {line}");
+                }
+            },
+            Err(err) => {
+                if !batch.is_dir() {
+                    return Err(err)
+                }
+                println!("  {err}");
+                errors.add(path, &err);
+            }
+        }
+    }
+
+    if merge_etf {
+        let (merged, conflicts) = match etf::merge_templates(&templates) {
+            Some(m) => m,
+            None => {
+                let msg = format!("(!) No templates to merge.");
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        };
+
+        if !conflicts.is_empty() {
+            println!("Conflicting definitions (first definition kept):");
+            for (i, conflict) in conflicts.iter().enumerate() {
+                println!("{:4}. {conflict}", i+1);
+            }
+        }
+
+        // Merged template is named after the input directory, or the single input file
+        let etf_path = match args.get_one::<PathBuf>("dir") {
+            Some(d) => {
+                let dir_name = match d.canonicalize()?.file_name() {
+                    Some(n) => n.to_string_lossy().to_string(),
+                    None => String::from("merged")
+                };
+                // Placed in the input directory, or relative to '--outdir'
+                batch.outpath(&d.join(format!("{dir_name}.etf")))?
+            },
+            None => batch.outpath(&batch.paths[0].with_extension("etf"))?
+        };
+        println!("{}", write_eaf(&merged, &etf_path)?);
+    }
+
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
+
+    Ok(())
+}
 
-    let mut eafs = match (path, dir) {
-        (None, Some(d)) => {
-            d.read_dir()?
-                .filter_map(|e| Eaf::read(&e.ok()?.path()).ok())
-                .collect::<Vec<_>>()
-        },
-        (Some(p), None) => vec![Eaf::read(p)?],
-        (..) => {
-            let msg = format!("Must choose one of 'eaf', 'dir'");
+/// Serializes and writes `eaf` to `path`.
+/// Returns a status message.
+fn write_eaf(eaf: &Eaf, path: &Path) -> std::io::Result<String> {
+    let eaf_str = match eaf.to_string(Some(4)) {
+        Ok(s) => s,
+        Err(err) => {
+            let msg = format!("(!) Failed to serialize '{}': {err}", path.display());
             return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
-        },
+        }
     };
 
-    for eaf in eafs.iter_mut() {
+    match writefile(eaf_str.as_bytes(), path) {
+        Ok(Some(p)) => Ok(format!("Wrote {}", p.display())),
+        Ok(None) => Ok(format!("Skipped writing {}", path.display())),
+        Err(err) => {
+            let msg = format!("(!) Failed to write '{}': {err}", path.display());
+            Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    }
+}
 
+/// Returns IDs for all tiers that descend from `tier_id`, excluding `tier_id`.
+fn descendants(eaf: &Eaf, tier_id: &str) -> Vec<String> {
+    let mut ids: Vec<String> = vec![tier_id.to_owned()];
+    loop {
+        let children: Vec<String> = eaf.tiers.iter()
+            .filter(|t| !ids.contains(&t.tier_id))
+            .filter(|t| t.parent_ref.as_ref().map(|p| ids.contains(p)).unwrap_or(false))
+            .map(|t| t.tier_id.to_owned())
+            .collect();
+        if children.is_empty() {
+            break
+        }
+        ids.extend(children);
     }
+    ids.remove(0);
+    ids
+}
 
-    Ok(())
-}
\ No newline at end of file
+/// Applies `edits` to a single ELAN-file.
+/// Returns the edited ELAN-file and a summary of the changes.
+fn edit(path: &Path, edits: &TierEdits) -> std::io::Result<(Eaf, Vec<String>)> {
+    let mut eaf = match Eaf::read(path) {
+        Ok(f) => f,
+        Err(err) => {
+            let msg = format!("(!) Failed to parse '{}': {err}", path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    if let Some(id) = edits.tier_id {
+        if eaf.get_tier(id).is_none() {
+            let msg = format!("(!) No tier with ID '{id}'. Available tiers: {}", eaf.tier_ids().join(", "));
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    }
+
+    // Tier language must refer to a defined language
+    if let Some(language) = edits.language {
+        if !eaf.languages.iter().any(|l| l.lang_id == language) {
+            let msg = format!("(!) No language with ID '{language}'. Available languages: {}",
+                eaf.languages.iter().map(|l| l.lang_id.as_str()).collect::<Vec<_>>().join(", "));
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    }
+
+    let mut summary: Vec<String> = Vec::new();
+
+    if edits.strip_referred {
+        let strip: Vec<String> = match edits.tier_id {
+            Some(id) => descendants(&eaf, id),
+            None => eaf.tiers.iter()
+                .filter(|t| t.is_ref())
+                .map(|t| t.tier_id.to_owned())
+                .collect(),
+        };
+        eaf.tiers.retain(|t| !strip.contains(&t.tier_id));
+        summary.push(format!("Stripped {} referred tiers", strip.len()));
+    }
+
+    let mut attr_count = 0;
+    for tier in eaf.tiers.iter_mut().filter(|t| edits.includes(&t.tier_id)) {
+        if let Some(annotator) = edits.annotator {
+            tier.annotator = Some(annotator.to_owned());
+        }
+        if let Some(participant) = edits.participant {
+            tier.participant = Some(participant.to_owned());
+        }
+        if let Some(language) = edits.language {
+            tier.lang_ref = Some(language.to_owned());
+        }
+        attr_count += 1;
+    }
+    if edits.annotator.is_some() || edits.participant.is_some() || edits.language.is_some() {
+        summary.push(format!("Set tier attributes for {attr_count} tiers"));
+    }
+
+    if edits.prefix.is_some() || edits.suffix.is_some() {
+        if let Err(err) = eaf.affix_tier_id_mut(edits.tier_id, edits.prefix, edits.suffix) {
+            let msg = format!("(!) Failed to affix tier IDs: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+        summary.push(format!(
+            "Affixed tier IDs: {}",
+            eaf.tier_ids().join(", ")
+        ));
+    }
+
+    Ok((eaf, summary))
+}
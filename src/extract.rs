@@ -11,22 +11,67 @@ use mp4iter::Mp4;
 
 use crate::{
     eaf::{
-        select_annotation, select_tier
+        can_prompt, resolve_annotation, resolve_tier
     },
     files::{
-        affix_file_name, append_file_name, confirm, writefile
+        affix_file_name, append_file_name, checked_outpath, confirm, writefile, Batch, ErrorReport
     }
 };
 
+/// Tier and annotation to use as boundary for extraction,
+/// if no start and end time is specified.
+struct Selection<'a> {
+    tier: Option<&'a str>,
+    annotation: Option<&'a str>,
+    interactive: bool,
+}
+
 /// extract main
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
-    let eaf_inpath = args.get_one::<PathBuf>("eaf").unwrap(); // ensured by clap
+    let batch = Batch::from_args(args)?;
     let start = args.get_one::<i64>("start").cloned();
     let end = args.get_one::<i64>("end").cloned();
     let tier_prefix = args.get_one::<String>("tier-prefix");
     let process = *args.get_one::<bool>("process-media").unwrap();
     let ffmpeg = args.get_one::<String>("ffmpeg").unwrap(); // ensured by clap
+    let selection = Selection {
+        tier: args.get_one::<String>("tier").map(|s| s.as_str()),
+        annotation: args.get_one::<String>("annotation").map(|s| s.as_str()),
+        interactive: *args.get_one::<bool>("interactive").unwrap(),
+    };
+
+    let mut errors = ErrorReport::new();
+    for eaf_inpath in batch.paths.iter() {
+        if batch.is_dir() {
+            println!("[{}]", eaf_inpath.display());
+        }
+        if let Err(err) = extract(eaf_inpath, start, end, &selection, tier_prefix, process, ffmpeg, &batch) {
+            if !batch.is_dir() {
+                return Err(err)
+            }
+            println!("{err}");
+            errors.add(eaf_inpath, &err);
+        }
+    }
 
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
+
+    Ok(())
+}
+
+/// Extracts a section of a single ELAN-file.
+fn extract(
+    eaf_inpath: &Path,
+    start: Option<i64>,
+    end: Option<i64>,
+    selection: &Selection,
+    tier_prefix: Option<&String>,
+    process: bool,
+    ffmpeg: &str,
+    batch: &Batch
+) -> std::io::Result<()> {
     let eaf = match Eaf::read(&eaf_inpath) {
         Ok(f) => f,
         Err(err) => {
@@ -41,7 +86,7 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
 
         // ...or select tier, then annotation to use as boundary for extraction
         _ => {
-            let tier = match select_tier(&eaf, true) {
+            let tier = match resolve_tier(&eaf, selection.tier, selection.interactive, true) {
                 Ok(t) => t,
                 Err(err) => {
                     let msg = format!("(!) Failed to extract tier: {err}");
@@ -50,14 +95,14 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
             };
 
             // let user choose whether to list large tiers
-            if tier.len() > 40 {
+            if tier.len() > 40 && selection.annotation.is_none() && can_prompt(selection.interactive) {
                 if !confirm(&format!("The tier '{}' has {} annotations. List all?", tier.tier_id, tier.len()))? {
                     let msg = format!("(!) Aborted process.");
                     return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
                 }
             }
 
-            let annotation = match select_annotation(&tier) {
+            let annotation = match resolve_annotation(&tier, selection.annotation, selection.interactive) {
                 Ok(a) => a,
                 Err(err) => {
                     let msg = format!("(!) Failed to extract annotation: {err}");
@@ -81,10 +126,12 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         .parent()
         .ok_or(std::io::Error::new(std::io::ErrorKind::Other, "Failed to extract parent"))?
         .join(eaf_infilestem);
-    if !eaf_outdir.exists() {
-        std::fs::create_dir_all(&eaf_outdir)?
+    let eaf_outpath = batch.outpath(&append_file_name(&eaf_outdir.join(eaf_infilestem).with_extension("eaf"), &timespan_str))?;
+    if let Some(outdir) = eaf_outpath.parent() {
+        if !outdir.exists() {
+            std::fs::create_dir_all(outdir)?
+        }
     }
-    let eaf_outpath = append_file_name(&eaf_outdir.join(eaf_infilestem).with_extension("eaf"), &timespan_str);
 
     // Cut eaf, optionally cut media and re-link new media file.
     let mut eaf_out = match eaf.extract(
@@ -135,8 +182,14 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
             let mpath_filename = mpath_out
                 .file_name()
                 .ok_or_else(|| err)?;
-            mpath_out = eaf_outpath
-                .with_file_name(mpath_filename);
+            let mpath_default = eaf_outpath.with_file_name(mpath_filename);
+            mpath_out = match checked_outpath(&mpath_default)? {
+                Some(p) => p,
+                None => {
+                    println!("Skipping media: '{}' already exists", mpath_default.display());
+                    continue;
+                }
+            };
 
             // Bounds checking video duration with extraction time span
             // But only MP4-files... which means wav files outside timespan
@@ -189,8 +242,8 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     };
 
     match writefile(&eaf_str.as_bytes(), &eaf_outpath) {
-        Ok(true) => println!("Wrote '{}'", eaf_outpath.display()),
-        Ok(false) => println!("Write to file aborted by user"),
+        Ok(Some(p)) => println!("Wrote '{}'", p.display()),
+        Ok(None) => println!("Skipped writing '{}'", eaf_outpath.display()),
         Err(err) => println!("(!) Failed to write '{}': {err}", eaf_outpath.display()),
     }
     // if let Some(outpath) = eaf_out.path() {
@@ -1,9 +1,11 @@
-use std::{ffi::OsString, fs::File, io::Write, path::{Path, PathBuf}};
+use std::{collections::HashMap, ffi::OsString, fs::File, io::{IsTerminal, Write}, path::{Path, PathBuf}, sync::OnceLock};
 
 use clap::builder::OsStr;
 use eaf_rs::Eaf;
 use inquire::{formatter::MultiOptionFormatter, list_option::ListOption, validator::Validation, MultiSelect};
 use itertools::Itertools;
+use regex::Regex;
+use walkdir::WalkDir;
 
 /// Used for any confirmation, e.g. overwrite file.
 pub fn confirm(message: &str) -> std::io::Result<bool> {
@@ -11,7 +13,11 @@ pub fn confirm(message: &str) -> std::io::Result<bool> {
         print!("{} (y/n): ", message);
         std::io::stdout().flush()?;
         let mut overwrite = String::new();
-        let _ = std::io::stdin().read_line(&mut overwrite)?;
+        // End of input, e.g. stdin closed or piped, counts as no
+        if std::io::stdin().read_line(&mut overwrite)? == 0 {
+            println!();
+            return Ok(false)
+        }
 
         return match overwrite.to_lowercase().trim() {
             "y" | "yes" => Ok(true),
@@ -29,22 +35,99 @@ pub fn has_extension(path: &Path, ext: &str) -> bool {
     path.extension().map(|s| s.to_ascii_lowercase()) == Some(OsString::from(&ext.to_lowercase()))
 }
 
-/// Write file with confirmation if path exists
-pub fn writefile(content: &[u8], path: &Path) -> std::io::Result<bool> {
-    // TODO return Result<bool> instead with Ok(true) = write success, Ok(false) = user aborted write
-    let write = if path.exists() {
-        confirm(&format!("{} already exists. Overwrite?", path.display()))?
-    } else {
-        true
-    };
+/// What to do when an output file already exists.
+/// Set once for the entire run via the global `--yes`, `--no-clobber`,
+/// and `--rename` flags.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Overwrite {
+    /// Prompt if stdin is a terminal, otherwise leave the existing file untouched.
+    #[default]
+    Ask,
+    /// Always overwrite existing files.
+    Always,
+    /// Never overwrite existing files.
+    Never,
+    /// Write to a new path with a numeric suffix, e.g. `file_1.eaf`.
+    Rename,
+}
+
+static OVERWRITE: OnceLock<Overwrite> = OnceLock::new();
+
+impl Overwrite {
+    /// Reads the overwrite policy from the global flags.
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        let flag = |id: &str| args.try_get_one::<bool>(id).ok().flatten().copied().unwrap_or(false);
+        if flag("yes") {
+            Self::Always
+        } else if flag("no-clobber") {
+            Self::Never
+        } else if flag("rename") {
+            Self::Rename
+        } else {
+            Self::Ask
+        }
+    }
 
-    if write {
-        let mut outfile = File::create(&path)?;
-        outfile.write_all(content)?;
-        return Ok(true)
+    /// Sets the policy for the remainder of the run.
+    /// Has no effect if a policy has already been set.
+    pub fn set(self) {
+        let _ = OVERWRITE.set(self);
     }
 
-    Ok(false)
+    /// Returns the current policy. Defaults to `Overwrite::Ask`.
+    pub fn get() -> Self {
+        OVERWRITE.get().copied().unwrap_or_default()
+    }
+}
+
+/// Resolves the path to write to according to the overwrite policy,
+/// if `path` already exists. Returns `None` if nothing should be written.
+/// Use before writing files via other means than `writefile()`,
+/// e.g. FFmpeg.
+pub fn checked_outpath(path: &Path) -> std::io::Result<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(Some(path.to_owned()))
+    }
+
+    match Overwrite::get() {
+        Overwrite::Always => Ok(Some(path.to_owned())),
+        Overwrite::Never => Ok(None),
+        Overwrite::Rename => {
+            let mut count = 1;
+            loop {
+                let renamed = append_file_name(path, &count.to_string());
+                if !renamed.exists() {
+                    return Ok(Some(renamed))
+                }
+                count += 1;
+            }
+        },
+        Overwrite::Ask => {
+            if !std::io::stdin().is_terminal() {
+                println!("{} already exists. Use '--yes' to overwrite, or '--rename' to write to a new file.", path.display());
+                return Ok(None)
+            }
+            match confirm(&format!("{} already exists. Overwrite?", path.display()))? {
+                true => Ok(Some(path.to_owned())),
+                false => Ok(None)
+            }
+        }
+    }
+}
+
+/// Writes file according to the overwrite policy if path exists.
+/// Returns the path written to, which differs from `path`
+/// if the policy is `Overwrite::Rename`, or `None` if the file was not written.
+pub fn writefile(content: &[u8], path: &Path) -> std::io::Result<Option<PathBuf>> {
+    let path = match checked_outpath(path)? {
+        Some(p) => p,
+        None => return Ok(None)
+    };
+
+    let mut outfile = File::create(&path)?;
+    outfile.write_all(content)?;
+
+    Ok(Some(path))
 }
 
 /// Checks if file name is hidden on Unix/Linux platforms.
@@ -96,6 +179,215 @@ pub fn file_stem_as_string(path: &Path) -> Option<String>{
     path.file_stem().map(|p| p.to_string_lossy().to_string())
 }
 
+/// Converts a simple file name glob pattern to a regular expression.
+/// Supports `*` (any number of characters) and `?` (any single character).
+/// All other characters are matched literally.
+pub fn glob2regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let rx = pattern.chars()
+        .map(|c| match c {
+            '*' => ".*".to_owned(),
+            '?' => ".".to_owned(),
+            c => regex::escape(&c.to_string())
+        })
+        .join("");
+    Regex::new(&format!("^{rx}$"))
+}
+
+/// Input paths and output locations for commands that process
+/// a single ELAN-file via `--eaf`, or multiple ELAN-files via `--dir`.
+/// 
+/// Output files are written next to each input file, unless `--outdir` is set,
+/// in which case the directory structure under `--dir` is mirrored in `--outdir`.
+/// For a single ELAN-file, `--out` replaces the derived output path.
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    /// ELAN-files to process.
+    pub paths: Vec<PathBuf>,
+    /// Input directory, if set.
+    dir: Option<PathBuf>,
+    /// Output directory, if set.
+    outdir: Option<PathBuf>,
+    /// Output file for a single ELAN-file, if set.
+    out: Option<PathBuf>,
+}
+
+impl Batch {
+    /// Collects ELAN-file paths from the `eaf`, `dir`, `recursive`, `glob`,
+    /// `outdir`, and `out` arguments. Hidden files are ignored.
+    pub fn from_args(args: &clap::ArgMatches) -> std::io::Result<Self> {
+        let eaf_path = args.get_one::<PathBuf>("eaf");
+        let dir = args.get_one::<PathBuf>("dir");
+        // 'recursive', 'glob', 'outdir', 'out' are not defined for all commands
+        let recursive = args.try_get_one::<bool>("recursive").ok().flatten().copied().unwrap_or(false);
+        let glob = match args.try_get_one::<String>("glob").ok().flatten() {
+            Some(g) => match glob2regex(g) {
+                Ok(rx) => Some(rx),
+                Err(err) => {
+                    let msg = format!("(!) '{g}' is not a valid glob pattern: {err}");
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+            },
+            None => None
+        };
+        let outdir = args.try_get_one::<PathBuf>("outdir").ok().flatten().cloned();
+        let out = args.try_get_one::<PathBuf>("out").ok().flatten().cloned();
+
+        let paths = match (eaf_path, dir) {
+            (Some(p), None) => {
+                if p.is_dir() {
+                    let msg = format!("(!) {} is a directory. Try '--dir <DIR>'.", p.display());
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+                vec![p.to_owned()]
+            },
+            (None, Some(d)) => {
+                if d.is_file() {
+                    let msg = format!("(!) {} is a file. Try '--eaf'.", d.display());
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+                let max_depth = if recursive {usize::MAX} else {1};
+                let mut paths: Vec<PathBuf> = WalkDir::new(d)
+                    .max_depth(max_depth)
+                    .into_iter()
+                    // ignore errors, would mostly be permission related issues
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path().to_owned())
+                    .filter(|p| has_extension(p, "eaf") && !is_hidden(p))
+                    .filter(|p| match (&glob, p.file_name()) {
+                        (Some(rx), Some(name)) => rx.is_match(&name.to_string_lossy()),
+                        (Some(_), None) => false,
+                        (None, _) => true
+                    })
+                    .collect();
+                paths.sort();
+                paths
+            },
+            _ => {
+                let msg = format!("(!) Must choose one of 'eaf', 'dir'.");
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        };
+
+        if paths.is_empty() {
+            let msg = format!("(!) No ELAN-files found.");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+
+        Ok(Self {
+            paths,
+            dir: dir.cloned(),
+            outdir,
+            out,
+        })
+    }
+
+    /// Returns `true` if files were collected from a directory.
+    pub fn is_dir(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Number of ELAN-files to process.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Relocates `path`, a default output path next to the input file,
+    /// to the output directory if one was specified. For directory input
+    /// the sub-directory structure under `dir` is preserved.
+    /// Returns `--out` as is if set.
+    /// Creates any missing parent directories.
+    pub fn outpath(&self, path: &Path) -> std::io::Result<PathBuf> {
+        if let Some(out) = &self.out {
+            return create_parent(out)
+        }
+
+        let outdir = match &self.outdir {
+            Some(d) => d,
+            None => return Ok(path.to_owned())
+        };
+
+        let relative = match &self.dir {
+            Some(d) => path.strip_prefix(d).ok().map(|p| p.to_owned()),
+            None => None
+        };
+
+        let outpath = match relative {
+            Some(rel) => outdir.join(rel),
+            None => match path.file_name() {
+                Some(name) => outdir.join(name),
+                None => {
+                    let msg = format!("(!) Failed to extract file name from '{}'", path.display());
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+            }
+        };
+
+        create_parent(&outpath)
+    }
+}
+
+/// Creates any missing parent directories for `path`
+/// and returns it.
+pub fn create_parent(path: &Path) -> std::io::Result<PathBuf> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            std::fs::create_dir_all(parent)?
+        }
+    }
+    Ok(path.to_owned())
+}
+
+/// Per-file errors for batch processing.
+/// Key: error message, value: paths that failed with that error.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorReport(HashMap<String, Vec<PathBuf>>);
+
+impl ErrorReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: &Path, err: &impl std::fmt::Display) {
+        self.0.entry(err.to_string()).or_insert(vec!()).push(path.to_owned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Total number of failed files.
+    pub fn len(&self) -> usize {
+        self.0.values().map(|v| v.len()).sum()
+    }
+
+    /// Prints a summary of processed files, followed by paths grouped per error.
+    pub fn print(&self, total: usize) {
+        print!("{}", self.summary(total))
+    }
+
+    /// Prints the summary to stderr instead, e.g. to keep stdout
+    /// free for structured output.
+    pub fn eprint(&self, total: usize) {
+        eprint!("{}", self.summary(total))
+    }
+
+    fn summary(&self, total: usize) -> String {
+        let mut summary = format!("Done. Processed {} of {total} files.", total - self.len());
+        if self.is_empty() {
+            summary.push_str(" No errors.\n")
+        } else {
+            summary.push_str("\n\nSome files failed due to errors:\n");
+            for (err, paths) in self.0.iter() {
+                summary.push_str(&format!("[ERR: {}]\n", err));
+                for path in paths {
+                    summary.push_str(&format!("  {}\n", path.display()))
+                }
+            }
+        }
+        summary
+    }
+}
+
 
 pub fn select_tiers(eaf: &Eaf) -> Result<Vec<String>, inquire::InquireError> {
     let options = eaf.tier_ids(); // .iter().map(|s| s.as_str()).collect_vec();
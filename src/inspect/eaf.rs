@@ -1,33 +1,174 @@
 //! Print an overview of an ELAN-file. The `verbose` flag also prints
 //! properties (in the header), controlled vocabularies etc.
 //! It is also possible to list all annotations in the selected tier.
+//! With `--format json|ndjson|tsv` tier statistics, or annotations
+//! in the selected tier, are printed as records instead.
 
-use std::path::PathBuf;
+use std::path::Path;
 
 use eaf_rs::eaf::{Eaf, controlled_vocabulary::CVType};
+use serde::Serialize;
 
 use crate::{
-    eaf::select_tier,
+    eaf::{can_prompt, resolve_tier},
     text::process_string,
-    files::confirm
+    files::{confirm, Batch, ErrorReport},
+    output::{print_records, Format}
 };
 
+/// Tier statistics, one record per tier.
+#[derive(Debug, Serialize)]
+struct TierRecord {
+    file: String,
+    tier_id: String,
+    parent: Option<String>,
+    linguistic_type: String,
+    tokenized: bool,
+    annotations: usize,
+    tokens_unique: usize,
+    tokens_total: usize,
+    participant: Option<String>,
+    annotator: Option<String>,
+}
+
+/// Annotation in selected tier, one record per annotation.
+#[derive(Debug, Serialize)]
+struct AnnotationRecord {
+    file: String,
+    tier_id: String,
+    /// 1-based index, i.e. the list number in the Grid tab in ELAN.
+    index: usize,
+    annotation_id: String,
+    start_ms: Option<i64>,
+    end_ms: Option<i64>,
+    value: String,
+}
+
 // Inspect EAF, main
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
-    let eaf_path = args.get_one::<PathBuf>("eaf"); // clap ensures value
+    let batch = Batch::from_args(args)?;
     let list_annotations = *args.get_one::<bool>("annotations").unwrap();
     let verbose = *args.get_one::<bool>("verbose").unwrap();
     let debug = *args.get_one::<bool>("debug").unwrap();
+    let tier_selector = args.get_one::<String>("tier").map(|s| s.as_str());
+    let interactive = *args.get_one::<bool>("interactive").unwrap();
+    let format = Format::from_args(args);
 
-    let eaf_path = match eaf_path {
-        Some(p) => p,
-        None => {
-            let msg = "No EAF file specified.";
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
-            
+    if format.is_structured() {
+        return run_structured(&batch, list_annotations, tier_selector, interactive, format)
+    }
+
+    let mut errors = ErrorReport::new();
+    for eaf_path in batch.paths.iter() {
+        if let Err(err) = inspect(eaf_path, list_annotations, tier_selector, interactive, verbose, debug) {
+            if !batch.is_dir() {
+                return Err(err)
+            }
+            println!("(!) {err}");
+            errors.add(eaf_path, &err);
         }
-    };
+    }
+
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
+
+    Ok(())
+}
+
+/// Prints tier statistics, or annotations in selected tier if `list_annotations`
+/// is set, as records for all ELAN-files. Errors are printed to stderr.
+fn run_structured(
+    batch: &Batch,
+    list_annotations: bool,
+    tier_selector: Option<&str>,
+    interactive: bool,
+    format: Format
+) -> std::io::Result<()> {
+    let mut tier_records: Vec<TierRecord> = Vec::new();
+    let mut annotation_records: Vec<AnnotationRecord> = Vec::new();
+
+    let mut errors = ErrorReport::new();
+    for eaf_path in batch.paths.iter() {
+        let eaf = match Eaf::read(eaf_path) {
+            Ok(f) => f,
+            Err(err) => {
+                let msg = format!("Failed to parse '{}': {err}", eaf_path.display());
+                if !batch.is_dir() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+                eprintln!("(!) {msg}");
+                errors.add(eaf_path, &err);
+                continue
+            }
+        };
+        let file = eaf_path.display().to_string();
+
+        if list_annotations {
+            let tier = match resolve_tier(&eaf, tier_selector, interactive, false) {
+                Ok(t) => t,
+                Err(err) => {
+                    let msg = format!("Failed to extract tier: {err}");
+                    if !batch.is_dir() {
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                    }
+                    eprintln!("(!) {msg}");
+                    errors.add(eaf_path, &msg);
+                    continue
+                }
+            };
+            for (i, annotation) in tier.annotations.iter().enumerate() {
+                let (start_ms, end_ms) = annotation.ts_val();
+                annotation_records.push(AnnotationRecord {
+                    file: file.to_owned(),
+                    tier_id: tier.tier_id.to_owned(),
+                    index: i+1,
+                    annotation_id: annotation.id(),
+                    start_ms,
+                    end_ms,
+                    value: annotation.to_str().to_owned(),
+                });
+            }
+        } else {
+            for tier in eaf.tiers.iter() {
+                tier_records.push(TierRecord {
+                    file: file.to_owned(),
+                    tier_id: tier.tier_id.to_owned(),
+                    parent: tier.parent_ref.to_owned(),
+                    linguistic_type: tier.linguistic_type_ref.to_owned(),
+                    tokenized: tier.is_tokenized(),
+                    annotations: tier.len(),
+                    tokens_unique: tier.tokens(None, None, true, true).len(),
+                    tokens_total: tier.tokens(None, None, false, false).len(),
+                    participant: tier.participant.to_owned(),
+                    annotator: tier.annotator.to_owned(),
+                });
+            }
+        }
+    }
+
+    if list_annotations {
+        print_records(&annotation_records, format)?;
+    } else {
+        print_records(&tier_records, format)?;
+    }
+
+    if batch.is_dir() {
+        errors.eprint(batch.len());
+    }
+
+    Ok(())
+}
 
+/// Inspect a single ELAN-file.
+fn inspect(
+    eaf_path: &Path,
+    list_annotations: bool,
+    tier_selector: Option<&str>,
+    interactive: bool,
+    verbose: bool,
+    debug: bool
+) -> std::io::Result<()> {
     let eaf = match Eaf::read(eaf_path) {
         Ok(f) => f,
         Err(err) => {
@@ -44,7 +185,7 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     }
 
     if list_annotations {
-        let tier = match select_tier(&eaf, false) {
+        let tier = match resolve_tier(&eaf, tier_selector, interactive, false) {
             Ok(t) => t,
             Err(err) => {
                 let msg = format!("Failed to extract tier: {err}");
@@ -53,7 +194,7 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         };
 
         // let user choose wheter to list large tiers
-        if tier.len() > 40 {
+        if tier.len() > 40 && tier_selector.is_none() && can_prompt(interactive) {
             if !confirm(&format!("The tier '{}' has {} annotations. List all?", tier.tier_id, tier.len()))? {
                 let msg = "Aborted process.";
                 return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
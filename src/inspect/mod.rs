@@ -6,7 +6,7 @@ pub mod tsconf;
 pub mod textgrid;
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
-    if args.get_one::<PathBuf>("eaf").is_some() {
+    if args.get_one::<PathBuf>("eaf").is_some() || args.get_one::<PathBuf>("dir").is_some() {
         return eaf::run(args)
     };
 
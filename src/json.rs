@@ -1,14 +1,34 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use eaf_rs::eaf::Eaf;
 
-use crate::files::writefile;
+use crate::files::{writefile, Batch, ErrorReport};
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
-    let eaf_path = args.get_one::<PathBuf>("eaf").unwrap(); // value ensured by clap
+    let batch = Batch::from_args(args)?;
 
-    let simple = args.contains_id("simple");
-    
+    let simple = *args.get_one::<bool>("simple").unwrap();
+
+    let mut errors = ErrorReport::new();
+    for eaf_path in batch.paths.iter() {
+        if let Err(err) = eaf2json(eaf_path, simple, &batch) {
+            if !batch.is_dir() {
+                return Err(err)
+            }
+            println!("{err}");
+            errors.add(eaf_path, &err);
+        }
+    }
+
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
+
+    Ok(())
+}
+
+/// Exports a single ELAN-file as JSON.
+fn eaf2json(eaf_path: &Path, simple: bool, batch: &Batch) -> std::io::Result<()> {
     let eaf = match Eaf::read(eaf_path) {
         Ok(f) => f,
         Err(err) => {
@@ -25,10 +45,14 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         }
     };
     
-    let json_path = eaf_path.with_extension("json");
-    if let Err(err) = writefile(&json.as_bytes(), &json_path) {
-        let msg = format!("(!) Failed to write '{}': {err}", eaf_path.display());
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    let json_path = batch.outpath(&eaf_path.with_extension("json"))?;
+    match writefile(&json.as_bytes(), &json_path) {
+        Ok(Some(p)) => println!("Wrote {}", p.display()),
+        Ok(None) => println!("Skipped writing {}", json_path.display()),
+        Err(err) => {
+            let msg = format!("(!) Failed to write '{}': {err}", json_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
     };
 
     Ok(())
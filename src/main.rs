@@ -11,6 +11,7 @@ mod ffmpeg;
 mod tokens;
 mod ngram;
 mod compare;
+mod der;
 mod media;
 mod clips;
 mod inspect;
@@ -22,8 +23,14 @@ mod json;
 mod color;
 mod whisper;
 mod whisper2eaf;
+mod rttm2eaf;
+mod eaf2rttm;
+mod spreadsheet;
 mod merge;
 mod edit;
+mod textgrid;
+mod subtitle;
+mod output;
 
 
 fn main() -> ExitCode {
@@ -46,7 +53,32 @@ eafutil csv2eaf --help
 - search:  Search annotations in one or more ELAN-files.
 - media:   Add or remove media paths.
 - inspect: Get an overview of an ELAN-file. Number of tiers, annotations, etc.
+- eaf2tg:  Convert an ELAN-file to a Praat TextGrid.
+- tg2eaf:  Convert a Praat TextGrid to an ELAN-file.
+
+Existing output files are only overwritten after confirmation, and never if stdin
+is not a terminal. Use '--yes', '--no-clobber', or '--rename' to set a policy.
 ")
+        .arg(Arg::new("yes")
+            .help("Overwrite existing output files without prompting.")
+            .long("yes")
+            .global(true)
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["no-clobber", "rename"])
+        )
+        .arg(Arg::new("no-clobber")
+            .help("Never overwrite existing output files.")
+            .long("no-clobber")
+            .global(true)
+            .action(ArgAction::SetTrue)
+            .conflicts_with("rename")
+        )
+        .arg(Arg::new("rename")
+            .help("Write to a new file with a numeric suffix if the output file exists, e.g. 'file_1.eaf'.")
+            .long("rename")
+            .global(true)
+            .action(ArgAction::SetTrue)
+        )
 
         .subcommand(Command::new("csv2eaf")
             .about("Convert a CSV-file containing annotation values and time stamps (annotation boundaries) to an ELAN-file. Requires headers and columns representing start, end, and annotation value, respectively. Annotations and time stamps should be listed in chronological order.
@@ -57,11 +89,13 @@ Accepted time formats in CSV-file:
 - milliseconds          unsigned integer                    174456
 - seconds.sub-seconds   float                               13.235
 
+Spreadsheets (.xlsx, .xlsm, .xlsb, .xls, .ods) are also accepted, with the same column options. Native time and duration cells are used as is. Number cells are parsed as in CSV-files: integers as milliseconds, decimals as seconds.
+
 ELAN defults to milliseconds internally.")
             .visible_alias("c2e")
 
             .arg(Arg::new("csv")
-                .help("Path to CSV-file.")
+                .help("Path to CSV-file or spreadsheet.")
                 .long("csv")
                 .short('c')
                 .required(true)
@@ -90,11 +124,28 @@ ELAN defults to milliseconds internally.")
                 .default_value("end") // deafult csv header for annotation end time
             )
             .arg(Arg::new("offset")
-                .help("Offset time in +/- milliseconds for start/end time stamps for imported main tier. Resulting start time stamps < 0ms will be set to 0ms, but if the end time stamp < 0ms the annotation will be discarded.")
+                .help("Offset time added to all start/end time stamps, as +/- milliseconds or [-]HH:MM:SS.fff, e.g. if time stamps are relative to the start of a clip. Rows with negative time stamps after offset raise an error, unless '--clamp' is set.")
                 .long("offset")
                 .short('o')
+                .allow_hyphen_values(true)
                 .default_value("0")
             )
+            .arg(Arg::new("offset-column")
+                .help("Name of column containing a per-row offset, added to '--offset'. Same formats as '--offset'. Empty cells are treated as 0.")
+                .long("offset-column")
+                .value_parser(clap::value_parser!(String))
+                .conflicts_with("rttm")
+            )
+            .arg(Arg::new("fix")
+                .help("Sort rows on start time and drop invalid rows, e.g. with invalid time stamps, end time not after start time, or overlapping an earlier row in the same tier, instead of raising an error. All problems are listed with line numbers either way.")
+                .long("fix")
+                .action(ArgAction::SetTrue)
+            )
+            .arg(Arg::new("clamp")
+                .help("Set start time stamps < 0 ms after offset to 0 ms, and discard rows with end time stamps <= 0 ms, instead of raising an error.")
+                .long("clamp")
+                .action(ArgAction::SetTrue)
+            )
             .arg(Arg::new("values")
                 .help("Name of column containing annotation values. Empty cells will generate empty annotations, with the specified time stamps in 'start' and 'end' columns.")
                 .long("values")
@@ -104,7 +155,7 @@ ELAN defults to milliseconds internally.")
                 .default_value("value") // deafult csv header for annotation value
             )
             .arg(Arg::new("ref-values")
-                .help("Name of columns containing referred tier annotation values. These must not exceed the number of rows in the 'values', 'start', 'end' columns. Multiple values can be specified, e.g.: '--refs col1 col2 col3', resulting in multiple referred tiers.")
+                .help("Name of columns containing referred tier annotation values, one referred tier (symbolic association) per column and main tier. Multiple values can be specified, e.g.: '--refs col1 col2 col3', resulting in multiple referred tiers. Use 'COLUMN:PARENT_COLUMN' for a referred tier with another referred tier as parent, e.g.: '--refs gloss pos:gloss'. Empty cells are skipped. With multiple main tiers, referred tier IDs are suffixed with '@' and the main tier ID.")
                 .long("refs")
                 .short('r')
                 .num_args(0..)
@@ -119,8 +170,14 @@ ELAN defults to milliseconds internally.")
                 .long("tier")
                 .value_parser(clap::value_parser!(String))
             )
+            .arg(Arg::new("sheet")
+                .help("Spreadsheet sheet to import, as sheet name or 1-based index. Defaults to the first sheet.")
+                .long("sheet")
+                .value_parser(clap::value_parser!(String))
+                .conflicts_with("rttm")
+            )
             .arg(Arg::new("rttm")
-                .help("Parses the CSV-file as a Rich Transcription Time Marked (RTTM) file.")
+                .help("Parses the CSV-file as a Rich Transcription Time Marked (RTTM) file. See 'rttm2eaf' for one tier per speaker, and one ELAN-file per file ID.")
                 .long("rttm")
                 .action(ArgAction::SetTrue)
             )
@@ -154,6 +211,11 @@ ELAN defults to milliseconds internally.")
                 .value_parser(clap::value_parser!(PathBuf))
                 // .takes_value(true)
             )
+            .arg(Arg::new("out")
+                .help("Output ELAN-file. Defaults to the CSV-file path with '.eaf' extension.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf))
+            )
         )
 
         .subcommand(Command::new("whisper2eaf")
@@ -201,6 +263,81 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .long("prefix-tiers")
                 .short('p')
                 .action(ArgAction::SetTrue))
+            .arg(Arg::new("out")
+                .help("Output ELAN-file. Only valid for a single JSON-file, or with '--join'. Defaults to the JSON-file path with '.eaf' extension, or the directory name if joined.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf)))
+        )
+
+        .subcommand(Command::new("rttm2eaf")
+            .about("Generates ELAN-files from a Rich Transcription Time Marked (RTTM) file, e.g. speaker diarization output. Each speaker becomes a tier with participant set. RTTM-files with multiple file IDs are split into one ELAN-file per file ID.")
+            .visible_alias("r2e")
+            .arg(Arg::new("rttm")
+                .help("RTTM-file.")
+                .long("rttm")
+                .short('r')
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("value")
+                .help("RTTM field to use as annotation value. Default: empty annotation values.")
+                .long("value")
+                .short('v')
+                .value_parser(["none", "ortho", "type", "speaker"])
+                .default_value("none"))
+            .arg(Arg::new("media")
+                .help("One or more media files to link. Media files with a file stem matching a file ID are only linked to the corresponding ELAN-file.")
+                .long("media")
+                .short('m')
+                .num_args(0..)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("out")
+                .help("Output ELAN-file. The file ID is appended to the file name if the RTTM-file contains multiple file IDs. Defaults to the RTTM-file path with '.eaf' extension.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf)))
+        )
+
+        .subcommand(Command::new("eaf2rttm")
+            .about("Generates a Rich Transcription Time Marked (RTTM) file from speaker tiers in the specified EAF, with one SPEAKER line per annotation. Referred tiers use the time spans of their parent annotations.")
+            .visible_alias("e2r")
+            .arg(Arg::new("eaf")
+                .help("ELAN-file to export to RTTM.")
+                .long("eaf")
+                .short('e')
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("tier")
+                .help("Speaker tier ID, or a regular expression matching tier IDs. Can be specified multiple times. Default: all main tiers.")
+                .long("tier")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(String)))
+            .arg(Arg::new("speaker")
+                .help("Speaker name source. 'participant' uses the tier ID for tiers without participant.")
+                .long("speaker")
+                .value_parser(["id", "participant"])
+                .default_value("id"))
+            .arg(Arg::new("file-id")
+                .help("File ID source, either the ELAN-file stem, or the file stem of the first linked media file.")
+                .long("file-id")
+                .value_parser(["stem", "media"])
+                .default_value("stem"))
+            .arg(Arg::new("merge")
+                .help("Merge segments by the same speaker separated by at most this many milliseconds. Overlapping segments are always merged if set.")
+                .long("merge")
+                .value_parser(clap::value_parser!(i64)))
+            .arg(Arg::new("min-duration")
+                .help("Drop segments shorter than this many milliseconds, after merging.")
+                .long("min-duration")
+                .value_parser(clap::value_parser!(i64))
+                .default_value("0"))
+            .arg(Arg::new("channel")
+                .help("Channel for tiers without a channel suffix '_ch<N>' in the tier ID, as generated by 'rttm2eaf' for multi-channel files.")
+                .long("channel")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"))
+            .arg(Arg::new("out")
+                .help("Output RTTM-file. Defaults to the ELAN-file path with '.rttm' extension.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf)))
         )
 
         .subcommand(Command::new("eaf2csv")
@@ -210,26 +347,38 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .help("ELAN-file to export to CSV.")
                 .long("eaf")
                 .short('e')
-                .required(true)
+                .required_unless_present("dir")
                 .value_parser(clap::value_parser!(PathBuf)))
             .arg(Arg::new("include")
-                .help("Customize which values and attributes to include.")
+                .help("Columns to include, in the specified order. Defaults to tier ID, size, parent, type, participant, annotator, annotation value, and start/end times.")
                 .long("include")
                 .short('i')
                 .num_args(1..)
                 .value_parser([
+                    "f", "file", // ELAN-file name
                     "ti", "tier-id",
                     "tt", "tier-type",
-                    "tl", "tier-length", // number of anntations in tier
+                    "tl", "tier-length", // number of annotations in tier
                     "tpnt", "tier-parent", // parent tier ID
-                    "ta", "tier-annotator", // number of anntations in tier
-                    "tprt", "tier-participant", // number of anntations in tier
+                    "ta", "tier-annotator",
+                    "tprt", "tier-participant",
+                    "tlng", "tier-language", // tier language reference
                     "ams", "annotation-time-ms", // annotation start, end, duration in ms
                     "ahms", "annotation-time-hms", // annotation start, end, duration in hh:mm:ss.fff
+                    "ad", "annotation-duration", // annotation duration in ms
+                    "ats", "annotation-time-slots", // time slot references
                     "av", "annotation-value",
                     "ai", "annotation-id", // internal annotation ID
+                    "apid", "annotation-parent-id", // parent annotation ID
+                    "apv", "annotation-parent-value", // parent annotation value
+                    "acv", "annotation-cv-entry", // controlled vocabulary entry ID
                 ])
-                .value_parser(clap::value_parser!(String)))
+                .conflicts_with("timeline"))
+            .arg(Arg::new("wide")
+                .help("Wide layout. One row per main tier annotation, with one column per referred tier containing the values of the referred annotations that belong to it.")
+                .long("wide")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("timeline"))
             .arg(Arg::new("delimiter")
                 .help("Delimiter used in CSV-file.")
                 .long("delimiter")
@@ -238,13 +387,100 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .value_parser(["tab", "comma", "semicolon"])
                 .default_value("comma"))
             .arg(Arg::new("timeline")
-                .help("Generates a timeline with one column per speaker.")
+                .help("Generates a time-aligned CSV with one column per selected tier, and one row per time interval, where intervals are delimited by all annotation boundaries in the selected tiers. Referred tiers use the time spans of their parent annotations.")
                 .long("timeline")
                 .action(ArgAction::SetTrue))
+            .arg(Arg::new("tier")
+                .help("Tiers to include in timeline. Tier IDs or regular expressions matching tier IDs. Prompts for tiers if not set and stdin is a terminal.")
+                .long("tier")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(String))
+                .requires("timeline"))
             .arg(Arg::new("interactive")
-                .help("Interactively choose which values to export.")
+                .help("Interactively choose which columns to export if '--include' is not set.")
                 .long("interactive")
                 .action(ArgAction::SetTrue))
+            .args(batch_args(true))
+        )
+
+        .subcommand(Command::new("eaf2tg")
+            .about("Generates a Praat TextGrid from the specified EAF. Each tier becomes an IntervalTier. Referred tiers use the time spans of their parent annotations. Gaps between annotations are filled with empty intervals.")
+            .visible_alias("e2t")
+            .arg(Arg::new("eaf")
+                .help("ELAN-file to export to TextGrid.")
+                .long("eaf")
+                .short('e')
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("out")
+                .help("Output TextGrid. Defaults to the ELAN-file path with '.TextGrid' extension.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf)))
+        )
+
+        .subcommand(Command::new("eaf2srt")
+            .about("Generates SubRip (SRT) subtitles from one or more tiers in the specified EAF. Annotations are split or merged into cues according to line length and cue duration. Overlapping tiers are written as separate tracks, one SRT-file per tier.")
+            .args(subtitle_args("srt"))
+        )
+
+        .subcommand(Command::new("eaf2vtt")
+            .about("Generates WebVTT subtitles from one or more tiers in the specified EAF. Annotations are split or merged into cues according to line length and cue duration. Multiple tiers are combined with voice tags ('<v Speaker>'), using tier participant or tier ID.")
+            .args(subtitle_args("vtt"))
+        )
+
+        .subcommand(Command::new("sub2eaf")
+            .about("Generates an EAF from SubRip (SRT) or WebVTT subtitles, with one annotation per cue. Cue settings and styling tags are removed. WebVTT voice spans ('<v Speaker>') can optionally be routed to one tier per voice.")
+            .visible_alias("s2e")
+            .arg(Arg::new("subtitles")
+                .help("SRT- or VTT-file to convert to EAF. The format is determined by file extension or WebVTT header.")
+                .long("subtitles")
+                .short('s')
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("tier")
+                .help("Tier ID for cues.")
+                .long("tier")
+                .value_parser(clap::value_parser!(String))
+                .default_value("subtitles"))
+            .arg(Arg::new("split-voices")
+                .help("Add each WebVTT voice to a tier of its own, with the voice as tier ID and participant. Cues without voice are added to the tier set via '--tier'.")
+                .long("split-voices")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("media")
+                .help("Media file to link in ELAN-file. Optional.")
+                .long("media")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("out")
+                .help("Output ELAN-file. Defaults to the subtitle file path with '.eaf' extension.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf)))
+        )
+
+        .subcommand(Command::new("tg2eaf")
+            .about("Generates an EAF from the specified Praat TextGrid. IntervalTiers become time-aligned tiers, with empty intervals discarded. PointTiers become time-aligned tiers with fixed-width annotations starting at each point.")
+            .visible_alias("t2e")
+            .arg(Arg::new("textgrid")
+                .help("Praat TextGrid to convert to EAF.")
+                .long("textgrid")
+                .short('t')
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("point-width")
+                .help("Annotation duration in milliseconds for points in PointTiers.")
+                .long("point-width")
+                .short('w')
+                .value_parser(clap::value_parser!(i64))
+                .default_value("50"))
+            .arg(Arg::new("media")
+                .help("Media file to link in ELAN-file. Optional.")
+                .long("media")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("out")
+                .help("Output ELAN-file. Defaults to the TextGrid path with '.eaf' extension.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf)))
         )
 
         .subcommand(Command::new("extract")
@@ -255,7 +491,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .long("eaf")
                 .short('e')
                 .value_parser(clap::value_parser!(PathBuf))
-                .required(true)
+                .required_unless_present("dir")
             )
             .arg(Arg::new("start")
                 .help("Start time of time span to extract in milliseconds. Must be a positive integer.")
@@ -284,6 +520,8 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .short('f')
                 .default_value(if cfg!(windows) {"ffmpeg.exe"} else {"ffmpeg"})
             )
+            .args(batch_args(true))
+            .args(selection_args(true))
         )
 
         .subcommand(Command::new("shift")
@@ -294,7 +532,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .long("eaf")
                 .short('e')
                 .value_parser(clap::value_parser!(PathBuf))
-                .required(true)
+                .required_unless_present("dir")
             )
             .arg(Arg::new("shift-value")
                 .help("Positive or negative millisecond value. Must be an integer.")
@@ -304,6 +542,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .allow_hyphen_values(true)
                 .required(true)
             )
+            .args(batch_args(true))
         )
 
         .subcommand(Command::new("search")
@@ -361,6 +600,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .short('v')
                 .action(ArgAction::SetTrue)
             )
+            .arg(format_arg())
         )
 
         .subcommand(Command::new("tokens")
@@ -424,11 +664,20 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .requires("distribution")
             )
             .arg(Arg::new("select-tier")
-                .help("Words for selected tier only.")
+                .help("Words for selected tier only. Specify a tier ID or a regular expression matching a single tier ID, or prompt for a tier if no value is given.")
                 .long("tier")
                 .short('t')
-                .action(clap::ArgAction::SetTrue)
+                .num_args(0..=1)
+                .default_missing_value("")
+                .value_parser(clap::value_parser!(String))
+            )
+            .arg(Arg::new("interactive")
+                .help("Always prompt for tier selection.")
+                .long("interactive")
+                .action(ArgAction::SetTrue)
             )
+            .args(batch_args(false))
+            .arg(format_arg())
         )
 
         .subcommand(Command::new("ngram")
@@ -473,6 +722,9 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .long("custom")
                 .value_parser(clap::value_parser!(String))
             )
+            .args(batch_args(false))
+            .args(selection_args(false))
+            .arg(format_arg())
         )
 
         .subcommand(Command::new("media")
@@ -532,6 +784,12 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .action(ArgAction::SetTrue)
                 .conflicts_with_all(&["add", "remove", "media", "scrub"])
             )
+            .arg(Arg::new("out")
+                .help("Output ELAN-file. Defaults to the input file name with a suffix for the media operation.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("dir")
+            )
         )
 
         .subcommand(Command::new("clips")
@@ -623,10 +881,11 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
             //     .long("wav")
             //     .short('w')
             // )
+            .args(selection_args(true))
         )
 
         .subcommand(Command::new("merge")
-            .about("Merge EAF-files.")
+            .about("Merge EAF-files. Tiers with the same ID are combined. Overlapping annotations in these abort the merge, unless '--on-overlap' is set.")
             .visible_alias("mg")
             .arg(Arg::new("eaf")
                 .help("ELAN-files to merge.")
@@ -655,6 +914,29 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .short('p')
                 .action(clap::ArgAction::SetTrue)
             )
+            .arg(Arg::new("map")
+                .help("Mapping file for renaming tiers before merging, so that tiers with different IDs across files are combined. One rule per line: '<tier ID or regular expression> = <new tier ID>'. Capture groups can be used in the new tier ID, e.g. '(.+)_speech = speech-$1'. Lines starting with '#' are ignored.")
+                .long("map")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["prefix-tiers", "by-participant"])
+            )
+            .arg(Arg::new("by-participant")
+                .help("Rename main tiers to their participant before merging, so that tiers with the same participant are combined. Tiers without a participant are left as is.")
+                .long("by-participant")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("prefix-tiers")
+            )
+            .arg(Arg::new("on-overlap")
+                .help("How to handle overlapping annotations in tiers with the same ID. 'abort' cancels the merge, 'prefer-first'/'prefer-last' keeps the annotation in the file listed first/last, 'join' joins overlapping annotations into one, 'keep-both' moves conflicting tiers to new tiers suffixed with the file name. Overlaps are always listed.")
+                .long("on-overlap")
+                .value_parser(["abort", "prefer-first", "prefer-last", "join", "keep-both"])
+                .default_value("abort")
+            )
+            .arg(Arg::new("out")
+                .help("Output ELAN-file. Defaults to 'merged_eaf.eaf' in the current directory.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf))
+            )
         )
 
         .subcommand(Command::new("inspect")
@@ -666,7 +948,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .short('e')
                 .value_parser(clap::value_parser!(PathBuf))
                 // .takes_value(true)
-                .required_unless_present_any(["pfsx", "tsconf", "textgrid"])
+                .required_unless_present_any(["pfsx", "tsconf", "textgrid", "dir"])
             )
             .arg(Arg::new("pfsx")
                 .help("ELAN preferences file.")
@@ -703,18 +985,35 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .short('d')
                 .action(clap::ArgAction::SetTrue)
             )
+            .args(batch_args(false))
+            .args(selection_args(false))
+            .arg(format_arg())
         )
 
         .subcommand(Command::new("compare")
-            .about("Compare annotation values of two tiers.")
+            .about("Compare annotation values of two tiers, in the same or in different ELAN-files, visually, via inter-annotator agreement metrics, or via word error rate.")
             .visible_alias("cmp")
             .arg(Arg::new("eaf")
-                .help("ELAN-file.")
+                .help("ELAN-file. Specify twice to compare tiers across two ELAN-files, the first one as reference. Tiers are then paired by tier ID, or via '--map'.")
                 .long("eaf")
                 .short('e')
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .required_unless_present("dir")
+            )
+            .arg(Arg::new("dir")
+                .help("Directory with ELAN-files. Specify twice, the first one as reference. ELAN-files are paired by file name, and agreement is aggregated over all pairs.")
+                .long("dir")
+                .short('d')
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("eaf")
+                .requires("agreement")
+            )
+            .arg(Arg::new("map")
+                .help("Mapping file for pairing tiers across ELAN-files, with one rule '<tier in first file> = <tier in second file>' per line. Tier IDs on the left may be regular expressions.")
+                .long("map")
                 .value_parser(clap::value_parser!(PathBuf))
-                // .takes_value(true)
-                .required(true)
             )
             .arg(Arg::new("timeline")
                 .help("Select and compare two tiers visually. Timeline based on start time of annotation. Requires all time slots to have values.")
@@ -724,7 +1023,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .conflicts_with("compact")
             )
             .arg(Arg::new("compact")
-                .help("Select and compare two tiers lengths visually. Compact. Default.")
+                .help("Select and compare two tiers visually. Compact. Default. Annotations are aligned on maximal temporal overlap, and each row is marked as deletion (DEL, first tier only), insertion (INS, second tier only), boundary shift in ms (SHIFT start/end), or value difference (DIFF).")
                 .long("compact")
                 .short('c')
                 .action(clap::ArgAction::SetTrue)
@@ -738,11 +1037,109 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .default_value("50")
                 // .takes_value(true)
             )
+            .arg(Arg::new("tier")
+                .help("Tier ID, or a regular expression matching a single tier ID. Specify twice, once for each tier to compare. Prompts for any missing tier if stdin is a terminal.")
+                .long("tier")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(String))
+            )
+            .arg(Arg::new("interactive")
+                .help("Always prompt for tiers not specified via '--tier'.")
+                .long("interactive")
+                .action(ArgAction::SetTrue)
+            )
+            .arg(Arg::new("agreement")
+                .help("Print inter-annotator agreement metrics instead, with the first tier as reference: Cohen's kappa and Krippendorff's alpha on time-sliced annotation values, segmentation overlap, boundary precision/recall/F-score, and a label confusion matrix.")
+                .long("agreement")
+                .short('a')
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["timeline", "compact"])
+            )
+            .arg(Arg::new("wer")
+                .help("Print word and character error rates (WER, CER) instead, per segment and overall, with the first tier as reference, e.g. a hand-corrected transcription, and the second as hypothesis, e.g. the 'segments' or 'words' tier generated by 'whisper2eaf'.")
+                .long("wer")
+                .short('w')
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["timeline", "compact", "agreement", "dir"])
+            )
+            .arg(Arg::new("alignment")
+                .help("Print the word alignment with substitutions (S), deletions (D), and insertions (I) for each segment with errors.")
+                .long("alignment")
+                .action(ArgAction::SetTrue)
+                .requires("wer")
+            )
+            .arg(Arg::new("ignore-case")
+                .help("Ignore case when calculating error rates.")
+                .long("ignore-case")
+                .short('i')
+                .action(ArgAction::SetTrue)
+                .requires("wer")
+            )
+            .arg(Arg::new("remove-common")
+                .help("Remove punctuation and brackets, such as '(', ')', '.', before calculating error rates.")
+                .long("remove")
+                .action(ArgAction::SetTrue)
+                .requires("wer")
+            )
+            .arg(Arg::new("remove-custom")
+                .help("Remove custom characters before calculating error rates. Specify as string, e.g. '.-='")
+                .long("custom")
+                .value_parser(clap::value_parser!(String))
+                .requires("wer")
+            )
+            .arg(Arg::new("slice")
+                .help("Time slice length in milliseconds for kappa, alpha, and confusion matrix.")
+                .long("slice")
+                .value_parser(clap::value_parser!(i64))
+                .default_value("10")
+            )
+            .arg(Arg::new("tolerance")
+                .help("Max distance in milliseconds for two annotation boundaries to match.")
+                .long("tolerance")
+                .value_parser(clap::value_parser!(i64).range(0..))
+                .default_value("200")
+            )
+            .arg(Arg::new("csv")
+                .help("Also write agreement metrics to this CSV-file. The confusion matrix is written to the same path with '_confusion' appended to the file name. In directory mode, only pooled confusion matrices are written.")
+                .long("csv")
+                .value_parser(clap::value_parser!(PathBuf))
+                .requires("agreement")
+            )
+        )
+
+        .subcommand(Command::new("der")
+            .about("Diarization error rate (DER) for speaker segments, with missed speech, false alarm, and speaker confusion. Reference and hypothesis can each be an RTTM-file, or an ELAN-file with one tier per speaker. Speakers are mapped one-to-one on maximal overlap, so names need not match.")
+            .arg(Arg::new("reference")
+                .help("Reference RTTM-file or ELAN-file.")
+                .long("reference")
+                .short('r')
+                .value_parser(clap::value_parser!(PathBuf))
+                .required(true)
+            )
+            .arg(Arg::new("hypothesis")
+                .help("Hypothesis RTTM-file or ELAN-file, e.g. speaker diarization output.")
+                .long("hypothesis")
+                .short('y')
+                .value_parser(clap::value_parser!(PathBuf))
+                .required(true)
+            )
+            .arg(Arg::new("collar")
+                .help("Time in milliseconds around each reference segment boundary that is not scored.")
+                .long("collar")
+                .short('c')
+                .value_parser(clap::value_parser!(i64))
+                .default_value("250")
+            )
+            .arg(Arg::new("tier")
+                .help("Speaker tier ID in ELAN-files, or a regular expression matching tier IDs. Can be specified multiple times. Default: all main tiers.")
+                .long("tier")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(String))
+            )
         )
 
         .subcommand(Command::new("edit")
-            .about("Edit tier attributes, strip referred tiers, generate an ELAN template file (ETF) etc.
-            Specify a tier ID to only affect that tier.")
+            .about("Edit tier attributes, strip referred tiers, generate an ELAN template file (ETF) etc. Specify a tier ID to only affect that tier. Edited files are written with '_edited' appended to the file name.")
             .arg(Arg::new("eaf")
                 .help("ELAN-file.")
                 .long("eaf")
@@ -750,13 +1147,6 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .value_parser(clap::value_parser!(PathBuf))
                 .required_unless_present("dir")
             )
-            .arg(Arg::new("dir")
-                .help("Directory with ELAN-files to edit. Not recursive.")
-                .long("dir")
-                .short('d')
-                .value_parser(clap::value_parser!(PathBuf))
-                .required_unless_present("eaf")
-            )
             .arg(Arg::new("tier-id")
                 .help("Tier to edit.")
                 .long("tier")
@@ -774,6 +1164,16 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .long("annotator")
                 .value_parser(clap::value_parser!(String))
             )
+            .arg(Arg::new("set-participant")
+                .help("Set participant attribute for tier/s.")
+                .long("participant")
+                .value_parser(clap::value_parser!(String))
+            )
+            .arg(Arg::new("set-language")
+                .help("Set language reference for tier/s. Must be a language ID defined in the ELAN-file, e.g. 'eng'.")
+                .long("language")
+                .value_parser(clap::value_parser!(String))
+            )
             .arg(Arg::new("prefix")
                 .help("Add prefix to tier IDs.")
                 .long("prefix")
@@ -785,10 +1185,18 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .value_parser(clap::value_parser!(String))
             )
             .arg(Arg::new("etf")
-                .help("Generate an ELAN template file (ETF) from specified ELAN file.")
+                .help("Generate an ELAN template file (ETF) from specified ELAN file. Contains tier hierarchy, linguistic types, constraints, controlled vocabularies, languages and locales, but no annotations. Other edits are applied first.")
                 .long("etf")
                 .action(clap::ArgAction::SetTrue)
             )
+            .arg(Arg::new("merge-etf")
+                .help("Merge the structures of all ELAN-files into a single ELAN template file, named after '--dir'. Conflicting definitions are reported, and the first definition kept.")
+                .long("merge-etf")
+                .action(clap::ArgAction::SetTrue)
+                .requires("etf")
+            )
+            .args(batch_args(true))
+            .mut_arg("dir", |a| a.short('d'))
         )
 
         .subcommand(Command::new("json")
@@ -800,7 +1208,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .short('e')
                 .value_parser(clap::value_parser!(PathBuf))
                 // .takes_value(true)
-                .required(true)
+                .required_unless_present("dir")
             )
             .arg(Arg::new("simple")
                 .help("Generate a simplified EAF structure as JSON.")
@@ -808,6 +1216,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .short('s')
                 .action(clap::ArgAction::SetTrue)
             )
+            .args(batch_args(true))
         )
 
         .subcommand(Command::new("tree")
@@ -818,11 +1227,15 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
                 .short('e')
                 .value_parser(clap::value_parser!(PathBuf))
                 // .takes_value(true)
-                .required(true)
+                .required_unless_present("dir")
             )
+            .args(batch_args(false))
         )
         .get_matches();
 
+    // Overwrite policy for existing output files, applies to all sub-commands
+    files::Overwrite::from_args(&args).set();
+
     //
     // CSV2EAF, generate eaf from csv
     //
@@ -843,6 +1256,26 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
         }
     }
 
+    //
+    // RTTM2EAF, generate eaf from rttm
+    //
+    if let Some(arg_matches) = args.subcommand_matches("rttm2eaf") {
+        if let Err(err) = rttm2eaf::run(&arg_matches) {
+            eprintln!("(!) 'rttm2eaf' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
+    //
+    // EAF2RTTM, generate rttm from eaf
+    //
+    if let Some(arg_matches) = args.subcommand_matches("eaf2rttm") {
+        if let Err(err) = eaf2rttm::run(&arg_matches) {
+            eprintln!("(!) 'eaf2rttm' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
     //
     // EAF2CSV2EAF, generate csv from eaf
     //
@@ -853,6 +1286,53 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
         }
     }
 
+    //
+    // EAF2TG, generate praat textgrid from eaf
+    //
+    if let Some(arg_matches) = args.subcommand_matches("eaf2tg") {
+        if let Err(err) = textgrid::eaf2tg::run(&arg_matches) {
+            eprintln!("(!) 'eaf2tg' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
+    //
+    // EAF2SRT, EAF2VTT, generate subtitles from eaf
+    //
+    if let Some(arg_matches) = args.subcommand_matches("eaf2srt") {
+        if let Err(err) = subtitle::eaf2sub::run(&arg_matches, subtitle::SubtitleFormat::Srt) {
+            eprintln!("(!) 'eaf2srt' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
+    if let Some(arg_matches) = args.subcommand_matches("eaf2vtt") {
+        if let Err(err) = subtitle::eaf2sub::run(&arg_matches, subtitle::SubtitleFormat::Vtt) {
+            eprintln!("(!) 'eaf2vtt' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
+    //
+    // SUB2EAF, generate eaf from subtitles
+    //
+    if let Some(arg_matches) = args.subcommand_matches("sub2eaf") {
+        if let Err(err) = subtitle::sub2eaf::run(&arg_matches) {
+            eprintln!("(!) 'sub2eaf' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
+    //
+    // TG2EAF, generate eaf from praat textgrid
+    //
+    if let Some(arg_matches) = args.subcommand_matches("tg2eaf") {
+        if let Err(err) = textgrid::tg2eaf::run(&arg_matches) {
+            eprintln!("(!) 'tg2eaf' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
     //
     // SHIFT, shift eaf specified milliseconds
     //
@@ -874,8 +1354,7 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
     }
 
     //
-    // MERGE, merge two eaf-files
-    // NOT IMPLEMENTED
+    // MERGE, merge two or more eaf-files
     //
     if let Some(arg_matches) = args.subcommand_matches("merge") {
         if let Err(err) = merge::run(&arg_matches) {
@@ -954,6 +1433,23 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
         }
     }
 
+    if let Some(arg_matches) = args.subcommand_matches("der") {
+        if let Err(err) = der::run(&arg_matches) {
+            eprintln!("(!) 'der' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
+    //
+    // EDIT, edit tier attributes, strip referred tiers
+    //
+    if let Some(arg_matches) = args.subcommand_matches("edit") {
+        if let Err(err) = edit::run(&arg_matches) {
+            eprintln!("(!) 'edit' exited with error: {err}");
+            return ExitCode::FAILURE
+        }
+    }
+
     //
     // JSON, generate JSON from EAF
     //
@@ -976,3 +1472,123 @@ Accepted Whisper JSON-format are either original Whisper JSON-files with with wo
 
     ExitCode::SUCCESS
 }
+
+/// Arguments for processing all ELAN-files in a directory,
+/// as an alternative to a single ELAN-file via `--eaf`.
+/// Set `outdir` for commands that write files, which also adds `out`
+/// for a single ELAN-file.
+fn batch_args(outdir: bool) -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("dir")
+            .help("Directory with ELAN-files to process. Hidden files are ignored.")
+            .long("dir")
+            .value_parser(clap::value_parser!(PathBuf))
+            .conflicts_with("eaf"),
+        Arg::new("recursive")
+            .help("Include ELAN-files in sub-directories of '--dir'.")
+            .long("recursive")
+            .action(ArgAction::SetTrue)
+            .requires("dir"),
+        Arg::new("glob")
+            .help("Only process ELAN-files in '--dir' with a file name matching the pattern, e.g. 'session_*.eaf'. Supports '*' and '?'.")
+            .long("glob")
+            .value_parser(clap::value_parser!(String))
+            .requires("dir"),
+    ];
+    if outdir {
+        args.push(
+            Arg::new("outdir")
+                .help("Output directory. Sub-directories under '--dir' are mirrored. Defaults to writing each output file next to the input ELAN-file.")
+                .long("outdir")
+                .value_parser(clap::value_parser!(PathBuf))
+        );
+        args.push(
+            Arg::new("out")
+                .help("Output file for a single ELAN-file. Defaults to a file name derived from the input ELAN-file.")
+                .long("out")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["dir", "outdir"])
+        );
+    }
+    args
+}
+
+/// Arguments for subtitle export, `extension` is the default
+/// output file extension.
+fn subtitle_args(extension: &str) -> Vec<Arg> {
+    vec![
+        Arg::new("eaf")
+            .help("ELAN-file to export to subtitles.")
+            .long("eaf")
+            .short('e')
+            .required(true)
+            .value_parser(clap::value_parser!(PathBuf)),
+        Arg::new("tier")
+            .help("Tier ID, or a regular expression matching tier IDs. Can be specified multiple times. Default: all main tiers.")
+            .long("tier")
+            .action(ArgAction::Append)
+            .value_parser(clap::value_parser!(String)),
+        Arg::new("max-line")
+            .help("Max number of characters per line.")
+            .long("max-line")
+            .value_parser(clap::value_parser!(usize))
+            .default_value("42"),
+        Arg::new("max-lines")
+            .help("Max number of lines per cue.")
+            .long("max-lines")
+            .value_parser(clap::value_parser!(usize))
+            .default_value("2"),
+        Arg::new("max-duration")
+            .help("Max cue duration in milliseconds.")
+            .long("max-duration")
+            .value_parser(clap::value_parser!(i64))
+            .default_value("7000"),
+        Arg::new("merge")
+            .help("Merge adjacent annotations in the same tier separated by at most this many milliseconds, if the result fits in a single cue.")
+            .long("merge")
+            .value_parser(clap::value_parser!(i64)),
+        Arg::new("tracks")
+            .help("Write one subtitle file per tier, with the tier ID appended to the file name.")
+            .long("tracks")
+            .action(ArgAction::SetTrue),
+        Arg::new("out")
+            .help(format!("Output file. Defaults to the ELAN-file path with '.{extension}' extension."))
+            .long("out")
+            .value_parser(clap::value_parser!(PathBuf)),
+    ]
+}
+
+/// Output format for commands that print tables,
+/// to optionally print records for use in other tools.
+fn format_arg() -> Arg {
+    Arg::new("format")
+        .help("Output format. 'table' prints human-readable tables, 'json' a JSON array, 'ndjson' one JSON object per line, 'tsv' tab separated values with headers. Status messages and errors are printed to stderr for all but 'table'.")
+        .long("format")
+        .value_parser(["table", "json", "ndjson", "tsv"])
+        .default_value("table")
+}
+
+/// Arguments for selecting a tier, and optionally an annotation in that tier,
+/// without prompting the user. Prompts are only used if no selection is specified
+/// and stdin is a terminal, or if `--interactive` is set.
+fn selection_args(annotation: bool) -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("tier")
+            .help("Tier ID, or a regular expression matching a single tier ID. Prompts for a tier if not set and stdin is a terminal.")
+            .long("tier")
+            .value_parser(clap::value_parser!(String)),
+        Arg::new("interactive")
+            .help("Always prompt for selections not specified via arguments.")
+            .long("interactive")
+            .action(ArgAction::SetTrue),
+    ];
+    if annotation {
+        args.push(
+            Arg::new("annotation")
+                .help("Annotation ID (e.g. 'a12') or index (the list number in the Grid tab in ELAN) in selected tier. Prompts for an annotation if not set and stdin is a terminal.")
+                .long("annotation")
+                .value_parser(clap::value_parser!(String))
+        )
+    }
+    args
+}
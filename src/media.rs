@@ -7,11 +7,12 @@ use std::{path::{Path, PathBuf}, ffi::OsStr};
 use eaf_rs::eaf::Eaf;
 use walkdir::WalkDir;
 
-use crate::files::{is_hidden, append_file_name, writefile};
+use crate::files::{is_hidden, append_file_name, create_parent, writefile};
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     let eaf_path = args.get_one::<PathBuf>("eaf"); // clap ensures value
     let eaf_dir = args.get_one::<PathBuf>("dir");
+    let out_path = args.get_one::<PathBuf>("out"); // conflicts with 'dir'
     let media_path = args.get_one::<PathBuf>("media");
     let remove = *args.get_one::<bool>("remove").unwrap(); // abs, all, conflicts with "add"
     let add = *args.get_one::<bool>("add").unwrap(); // abs, all, conflict with "remove"
@@ -108,11 +109,18 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                 return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
             }
         };
-        let eaf_outpath = append_file_name(path, filename_suffix);
+        let eaf_outpath = match out_path {
+            Some(p) => create_parent(p)?,
+            None => append_file_name(path, filename_suffix)
+        };
         
-        if let Err(err) = writefile(&eaf_str.as_bytes(), &eaf_outpath) {
-            let msg = format!("(!) Failed to write '{}': {err}", eaf_outpath.display());
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        match writefile(&eaf_str.as_bytes(), &eaf_outpath) {
+            Ok(Some(p)) => println!("Wrote {}", p.display()),
+            Ok(None) => println!("Skipped writing {}", eaf_outpath.display()),
+            Err(err) => {
+                let msg = format!("(!) Failed to write '{}': {err}", eaf_outpath.display());
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
         }
 
         println!("Resulting media paths in '{}':", path.display());
@@ -0,0 +1,176 @@
+//! Map tier IDs across ELAN-files before merging, so that tiers with
+//! inconsistent names can be combined, while unrelated tiers stay separate.
+//!
+//! Tiers are mapped either via a mapping file, or by participant.
+//! A mapping file has one rule per line in the form `<source> = <target>`,
+//! where source is a tier ID or a regular expression fully matching tier IDs,
+//! and target is the new tier ID. Capture groups can be used in the target,
+//! e.g. `$1`. Empty lines and lines starting with `#` are ignored.
+//! The first matching rule is used.
+//!
+//! ```text
+//! # combine speech tiers for speaker A
+//! A_speech = speaker-A
+//! (.+)_gesture = gesture-$1
+//! ```
+
+use std::path::Path;
+
+use eaf_rs::Eaf;
+use regex::Regex;
+
+/// Tier ID mapping rules read from a mapping file.
+#[derive(Debug, Clone)]
+pub struct TierMap {
+    /// `(source, source as regex, target)`
+    rules: Vec<(String, Regex, String)>,
+}
+
+impl TierMap {
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut rules: Vec<(String, Regex, String)> = Vec::new();
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let (source, target) = match line.split_once('=') {
+                Some((s, t)) if !s.trim().is_empty() && !t.trim().is_empty() => (s.trim(), t.trim()),
+                _ => {
+                    let msg = format!("(!) Invalid rule on line {} in '{}': expected '<source> = <target>'", i+1, path.display());
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+            };
+            let rx = match Regex::new(&format!("^(?:{source})$")) {
+                Ok(rx) => rx,
+                Err(err) => {
+                    let msg = format!("(!) Invalid pattern '{source}' on line {} in '{}': {err}", i+1, path.display());
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+            };
+            rules.push((source.to_owned(), rx, target.to_owned()));
+        }
+
+        if rules.is_empty() {
+            let msg = format!("(!) No rules in '{}'", path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+
+        Ok(Self { rules })
+    }
+
+    /// Returns the new ID for `tier_id` according to the first matching rule,
+    /// or `None` if no rule matches.
+    pub fn target(&self, tier_id: &str) -> Option<String> {
+        self.rules.iter()
+            .find_map(|(source, rx, target)| {
+                if source == tier_id {
+                    Some(target.to_owned())
+                } else if rx.is_match(tier_id) {
+                    Some(rx.replace(tier_id, target.as_str()).to_string())
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+/// Returns `(old ID, new ID)` for tiers in `eaf` mapped via `map`.
+pub fn map_by_rules(eaf: &Eaf, map: &TierMap) -> Vec<(String, String)> {
+    eaf.tiers.iter()
+        .filter_map(|t| Some((t.tier_id.to_owned(), map.target(&t.tier_id)?)))
+        .filter(|(old, new)| old != new)
+        .collect()
+}
+
+/// Returns `(old ID, new ID)` for main tiers in `eaf` with a participant set,
+/// where the new ID is the participant. Fails if multiple main tiers
+/// in the same file have the same participant.
+///
+/// Dependent tiers follow their main tier: if the dependent tier ID split on `@`
+/// contains the main tier ID, that part is replaced with the participant,
+/// e.g. `gloss@A` becomes `gloss@Anna`. Otherwise the participant is appended,
+/// e.g. `gloss` becomes `gloss@Anna`.
+pub fn map_by_participant(eaf: &Eaf) -> std::io::Result<Vec<(String, String)>> {
+    let mut mapping: Vec<(String, String)> = Vec::new();
+    for tier in eaf.tiers.iter().filter(|t| t.parent_ref.is_none()) {
+        let participant = match tier.participant.as_deref().map(|p| p.trim()) {
+            Some(p) if !p.is_empty() => p,
+            _ => continue
+        };
+        if let Some((other, _)) = mapping.iter().find(|(_, p)| p == participant) {
+            let msg = format!("(!) Tiers '{other}' and '{}' have the same participant '{participant}'. Use a mapping file instead.", tier.tier_id);
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+        mapping.push((tier.tier_id.to_owned(), participant.to_owned()));
+    }
+    mapping.retain(|(old, new)| old != new);
+
+    // Carry mapping over to dependent tiers
+    let mut dependent: Vec<(String, String)> = Vec::new();
+    for tier in eaf.tiers.iter().filter(|t| t.parent_ref.is_some()) {
+        let main_id = main_tier_id(eaf, &tier.tier_id);
+        if let Some((old, new)) = mapping.iter().find(|(old, _)| old == &main_id) {
+            let tier_id = match tier.tier_id.split('@').any(|part| part == old) {
+                true => tier.tier_id.split('@')
+                    .map(|part| if part == old { new.as_str() } else { part })
+                    .collect::<Vec<_>>()
+                    .join("@"),
+                false => format!("{}@{new}", tier.tier_id)
+            };
+            dependent.push((tier.tier_id.to_owned(), tier_id));
+        }
+    }
+    mapping.extend(dependent);
+
+    Ok(mapping)
+}
+
+/// Returns the ID of the main tier `tier_id` depends on,
+/// or `tier_id` if it is a main tier.
+fn main_tier_id(eaf: &Eaf, tier_id: &str) -> String {
+    let mut id = tier_id.to_owned();
+    // Limit depth in case of circular references
+    for _ in 0..eaf.tiers.len() {
+        match eaf.get_tier(&id).and_then(|t| t.parent_ref.to_owned()) {
+            Some(parent) => id = parent,
+            None => break
+        }
+    }
+    id
+}
+
+/// Renames tiers in `eaf` according to `mapping` (old ID, new ID),
+/// including parent references in referred tiers, and tier references
+/// in annotations.
+/// Fails if two tiers would end up with the same ID.
+pub fn rename_tiers(eaf: &mut Eaf, mapping: &[(String, String)]) -> std::io::Result<()> {
+    let new_id = |id: &str| mapping.iter()
+        .find(|(old, _)| old == id)
+        .map(|(_, new)| new.to_owned())
+        .unwrap_or_else(|| id.to_owned());
+
+    let mut ids: Vec<String> = eaf.tiers.iter()
+        .map(|t| new_id(&t.tier_id))
+        .collect();
+    ids.sort();
+    if let Some(w) = ids.windows(2).find(|w| w[0] == w[1]) {
+        let msg = format!("(!) Multiple tiers would be renamed to '{}'", w[0]);
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
+
+    for tier in eaf.tiers.iter_mut() {
+        tier.tier_id = new_id(&tier.tier_id);
+        tier.parent_ref = tier.parent_ref.as_deref().map(|p| new_id(p));
+        let tier_id = tier.tier_id.to_owned();
+        tier.annotations.iter_mut()
+            .for_each(|a| a.set_tier_id(&tier_id));
+    }
+
+    // Update derived state, e.g. annotation index
+    eaf.index();
+
+    Ok(())
+}
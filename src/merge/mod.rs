@@ -0,0 +1,427 @@
+//! Merges two or more ELAN-files. Tiers with the same ID are combined.
+//! If annotations in tiers with the same ID overlap, the merge is aborted by default.
+//! Optionally prioritise to preserve annotations in the first or last file, join
+//! the overlapping annotations, or keep both by moving conflicting tiers to
+//! new tiers suffixed with the file name.
+//! Overlapping annotations are always listed before writing the merged file.
+//! Important: Time slots with no time value set will be discarded.
+//!
+//! Tiers with inconsistent IDs across files can be combined by mapping
+//! tier IDs before merging, either via a mapping file or by participant
+//! (see `mapping`).
+
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use eaf_rs::Eaf;
+
+use crate::files::{checked_outpath, create_parent, file_stem_as_string, has_extension, is_hidden};
+
+pub mod mapping;
+
+use mapping::{map_by_participant, map_by_rules, rename_tiers, TierMap};
+
+/// How to handle overlapping annotations in tiers with the same ID.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OnOverlap {
+    /// Abort the merge.
+    Abort,
+    /// Keep the annotation in the file listed first, discard the other.
+    PreferFirst,
+    /// Keep the annotation in the file listed last, discard the other.
+    PreferLast,
+    /// Join overlapping annotations into one, spanning both,
+    /// with values joined in chronological order.
+    Join,
+    /// Keep both by suffixing tier IDs with the file name
+    /// for tiers that overlap with a tier in an earlier file.
+    KeepBoth,
+}
+
+impl OnOverlap {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "abort" => Some(Self::Abort),
+            "prefer-first" => Some(Self::PreferFirst),
+            "prefer-last" => Some(Self::PreferLast),
+            "join" => Some(Self::Join),
+            "keep-both" => Some(Self::KeepBoth),
+            _ => None
+        }
+    }
+}
+
+/// Time-aligned annotation in one of the input files.
+#[derive(Debug, Clone)]
+struct Span {
+    /// Index of the input file.
+    file: usize,
+    annotation_id: String,
+    start: i64,
+    end: i64,
+    value: String,
+}
+
+impl Span {
+    fn overlaps(&self, other: &Span) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// Two overlapping annotations in tiers with the same ID in different files.
+#[derive(Debug, Clone)]
+struct Overlap {
+    tier_id: String,
+    first: Span,
+    second: Span,
+}
+
+pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
+    let paths = args.get_many::<PathBuf>("eaf");
+    let dir = args.get_one::<PathBuf>("dir");
+    // optionally prefix tier IDs with filestem to avoid
+    // combining tiers with same ID but distinct content
+    let prefix_tiers = *args.get_one::<bool>("prefix-tiers").unwrap();
+    let media_paths: Vec<PathBuf> = args.get_many::<PathBuf>("media")
+        .map(|m| m.into_iter().map(|p| p.into()).collect())
+        .unwrap_or_default();
+    let on_overlap = args.get_one::<String>("on-overlap")
+        .and_then(|s| OnOverlap::from_str(s))
+        .unwrap(); // clap default 'abort'
+    let tier_map = match args.get_one::<PathBuf>("map") {
+        Some(p) => Some(TierMap::read(p)?),
+        None => None
+    };
+    let by_participant = *args.get_one::<bool>("by-participant").unwrap();
+
+    // Get EAF paths. Order matters for 'prefer-first', 'prefer-last'.
+    let eaf_paths: Vec<PathBuf> = match (paths, dir) {
+        (None, Some(d)) => {
+            let mut p: Vec<PathBuf> = d.read_dir()?
+                .filter_map(|e| Some(e.ok()?.path()))
+                .filter(|p| has_extension(p, "eaf") && !is_hidden(p))
+                .collect();
+            p.sort();
+            p
+        },
+        (Some(ps), None) => ps.cloned().collect(),
+        (..) => {
+            let msg = format!("Must choose one of 'eaf', 'dir'");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        },
+    };
+
+    if eaf_paths.len() < 2 {
+        let msg = format!("(!) At least two ELAN-files are required for merging.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
+
+    let mut eafs: Vec<Eaf> = Vec::new();
+    for path in eaf_paths.iter() {
+        let mut eaf = match Eaf::read(path) {
+            Ok(f) => f,
+            Err(err) => {
+                let msg = format!("(!) Failed to parse '{}': {err}", path.display());
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        };
+        if prefix_tiers {
+            if let Some(stem) = file_stem_as_string(path) {
+                eaf.prefix_tier_all_mut(&stem)?;
+            }
+        }
+
+        // 'map', 'by-participant', 'prefix-tiers' are mutually exclusive
+        let tier_mapping = match (&tier_map, by_participant) {
+            (Some(m), false) => map_by_rules(&eaf, m),
+            (None, true) => map_by_participant(&eaf).map_err(|err| {
+                let msg = format!("{err} ('{}')", path.display());
+                std::io::Error::new(std::io::ErrorKind::Other, msg)
+            })?,
+            _ => Vec::new()
+        };
+        if !tier_mapping.is_empty() {
+            if let Err(err) = rename_tiers(&mut eaf, &tier_mapping) {
+                let msg = format!("{err} ('{}')", path.display());
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+            println!("[{}]", path.display());
+            for (old, new) in tier_mapping.iter() {
+                println!("  '{old}' -> '{new}'");
+            }
+        }
+
+        eafs.push(eaf);
+    }
+
+    let overlaps = find_overlaps(&eafs);
+
+    if !overlaps.is_empty() {
+        print_overlaps(&overlaps, &eaf_paths);
+        match on_overlap {
+            OnOverlap::Abort => {
+                let msg = format!("(!) Found {} overlapping annotations. Use '--on-overlap' to choose how to resolve these.", overlaps.len());
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            },
+            OnOverlap::PreferFirst => prefer(&mut eafs, false),
+            OnOverlap::PreferLast => prefer(&mut eafs, true),
+            OnOverlap::Join => join(&mut eafs),
+            OnOverlap::KeepBoth => keep_both(&mut eafs, &overlaps, &eaf_paths)?,
+        }
+    }
+
+    let mut eaf = Eaf::merge(&eafs)?;
+
+    // Add optional media files
+    media_paths.iter()
+        .try_for_each(|p| eaf.add_media(p, None))?;
+
+    let outpath = match args.get_one::<PathBuf>("out") {
+        Some(p) => create_parent(p)?,
+        None => PathBuf::from("merged_eaf.eaf")
+    };
+    match checked_outpath(&outpath)? {
+        Some(p) => {
+            eaf.write(&p, Some(4))?;
+            println!("Wrote {}", p.display());
+        },
+        None => println!("Skipped writing {}", outpath.display())
+    }
+
+    Ok(())
+}
+
+/// Returns time-aligned annotations in tier `tier_id` in `eaf`,
+/// sorted on start time. Referred tiers return no annotations.
+fn spans(eaf: &Eaf, file: usize, tier_id: &str) -> Vec<Span> {
+    let mut spans: Vec<Span> = eaf.get_tier(tier_id)
+        .filter(|t| !t.is_ref())
+        .map(|t| t.annotations.iter()
+            .filter_map(|a| match a.ts_val() {
+                (Some(start), Some(end)) => Some(Span {
+                    file,
+                    annotation_id: a.id(),
+                    start,
+                    end,
+                    value: a.to_str().to_owned(),
+                }),
+                _ => None
+            })
+            .collect()
+        )
+        .unwrap_or_default();
+    spans.sort_by_key(|s| (s.start, s.end));
+    spans
+}
+
+/// Returns all tier IDs across `eafs`, in order of first occurrence.
+fn tier_ids(eafs: &[Eaf]) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for id in eafs.iter().flat_map(|e| e.tier_ids()) {
+        if !ids.contains(&id) {
+            ids.push(id)
+        }
+    }
+    ids
+}
+
+/// Finds overlapping annotations in tiers with the same ID in different files.
+/// Referred tiers follow their parent tier and are ignored.
+fn find_overlaps(eafs: &[Eaf]) -> Vec<Overlap> {
+    let mut overlaps: Vec<Overlap> = Vec::new();
+    for tier_id in tier_ids(eafs).iter() {
+        let file_spans: Vec<Vec<Span>> = eafs.iter()
+            .enumerate()
+            .map(|(i, e)| spans(e, i, tier_id))
+            .collect();
+        for (i, spans1) in file_spans.iter().enumerate() {
+            for spans2 in file_spans.iter().skip(i+1) {
+                for s1 in spans1.iter() {
+                    for s2 in spans2.iter().filter(|s2| s1.overlaps(s2)) {
+                        overlaps.push(Overlap {
+                            tier_id: tier_id.to_owned(),
+                            first: s1.to_owned(),
+                            second: s2.to_owned(),
+                        })
+                    }
+                }
+            }
+        }
+    }
+    overlaps
+}
+
+fn print_overlaps(overlaps: &[Overlap], paths: &[PathBuf]) {
+    let name = |i: usize| paths.get(i)
+        .map(|p| display_name(p))
+        .unwrap_or_default();
+    println!("Overlapping annotations:");
+    for (i, overlap) in overlaps.iter().enumerate() {
+        println!("{:4}. Tier '{}'", i+1, overlap.tier_id);
+        for span in [&overlap.first, &overlap.second] {
+            println!("      {:8} - {:8} ms  {} ({}): '{}'",
+                span.start,
+                span.end,
+                name(span.file),
+                span.annotation_id,
+                span.value
+            );
+        }
+    }
+}
+
+/// Removes annotations with the specified IDs from `eaf`, together with
+/// any annotations in referred tiers that depend on these.
+fn remove_annotations(eaf: &mut Eaf, ids: &HashSet<String>) {
+    let mut remove = ids.to_owned();
+    loop {
+        let children: Vec<String> = eaf.tiers.iter()
+            .flat_map(|t| t.annotations.iter())
+            .filter(|a| !remove.contains(&a.id()))
+            .filter(|a| a.ref_id().map(|r| remove.contains(&r)).unwrap_or(false))
+            .map(|a| a.id())
+            .collect();
+        if children.is_empty() {
+            break
+        }
+        remove.extend(children);
+    }
+    for tier in eaf.tiers.iter_mut() {
+        tier.annotations.retain(|a| !remove.contains(&a.id()))
+    }
+}
+
+/// Discards annotations that overlap with an annotation in a tier with the same ID
+/// in a file with higher priority. Files listed first have priority,
+/// unless `last` is set.
+fn prefer(eafs: &mut [Eaf], last: bool) {
+    let mut order: Vec<usize> = (0..eafs.len()).collect();
+    if last {
+        order.reverse();
+    }
+
+    for tier_id in tier_ids(eafs).iter() {
+        // Annotations kept so far in files with higher priority
+        let mut kept: Vec<Span> = Vec::new();
+        for i in order.iter() {
+            let (keep, discard): (Vec<Span>, Vec<Span>) = spans(&eafs[*i], *i, tier_id)
+                .into_iter()
+                .partition(|s| !kept.iter().any(|k| k.overlaps(s)));
+            let ids: HashSet<String> = discard.into_iter()
+                .map(|s| s.annotation_id)
+                .collect();
+            remove_annotations(&mut eafs[*i], &ids);
+            kept.extend(keep);
+        }
+    }
+}
+
+/// Joins overlapping annotations in tiers with the same ID into the annotation
+/// that starts first. The joined annotation spans all overlapping annotations,
+/// and values are joined in chronological order.
+/// Annotations in referred tiers that depend on the discarded annotations are removed.
+fn join(eafs: &mut [Eaf]) {
+    for tier_id in tier_ids(eafs).iter() {
+        let mut all: Vec<Span> = eafs.iter()
+            .enumerate()
+            .flat_map(|(i, e)| spans(e, i, tier_id))
+            .collect();
+        all.sort_by_key(|s| (s.start, s.end, s.file));
+
+        // Group transitively overlapping annotations
+        let mut groups: Vec<Vec<Span>> = Vec::new();
+        for span in all.into_iter() {
+            match groups.last_mut() {
+                Some(group) if group.iter().any(|s| s.overlaps(&span)) => group.push(span),
+                _ => groups.push(vec![span])
+            }
+        }
+
+        for group in groups.iter().filter(|g| g.len() > 1) {
+            let first = &group[0];
+            let end = group.iter().map(|s| s.end).max().unwrap_or(first.end);
+            let value = group.iter()
+                .map(|s| s.value.as_str())
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            if let Some(annotation) = eafs[first.file].tiers.iter_mut()
+                .find(|t| &t.tier_id == tier_id)
+                .and_then(|t| t.annotations.iter_mut().find(|a| a.id() == first.annotation_id))
+            {
+                annotation.set_ts_val(Some(first.start), Some(end));
+                annotation.set_value(&value);
+            }
+
+            for span in group.iter().skip(1) {
+                let ids = HashSet::from([span.annotation_id.to_owned()]);
+                remove_annotations(&mut eafs[span.file], &ids);
+            }
+        }
+    }
+}
+
+/// Returns IDs for `tier_id` and all tiers in `eaf` that depend on it,
+/// directly or via other referred tiers.
+fn subtree(eaf: &Eaf, tier_id: &str) -> Vec<String> {
+    let mut ids = vec![tier_id.to_owned()];
+    let mut i = 0;
+    while let Some(parent) = ids.get(i).cloned() {
+        for tier in eaf.tiers.iter() {
+            if tier.parent_ref.as_deref() == Some(parent.as_str()) && !ids.contains(&tier.tier_id) {
+                ids.push(tier.tier_id.to_owned());
+            }
+        }
+        i += 1;
+    }
+    ids
+}
+
+/// Suffixes tier IDs with the file name for tiers that overlap
+/// with a tier with the same ID in an earlier file,
+/// so that both sets of annotations are kept in separate tiers.
+/// Referred tiers of a renamed tier get the same suffix.
+fn keep_both(eafs: &mut [Eaf], overlaps: &[Overlap], paths: &[PathBuf]) -> std::io::Result<()> {
+    let mut rename: Vec<(usize, String)> = overlaps.iter()
+        .map(|o| (o.second.file, o.tier_id.to_owned()))
+        .collect();
+    rename.sort();
+    rename.dedup();
+
+    // Tiers in the subtree of another renamed tier are renamed with it
+    let subtrees: Vec<(usize, Vec<String>)> = rename.iter()
+        .map(|(file, tier_id)| (*file, subtree(&eafs[*file], tier_id)))
+        .collect();
+    rename.retain(|(file, tier_id)| !subtrees.iter()
+        .any(|(f, ids)| f == file && ids.iter().skip(1).any(|id| id == tier_id))
+    );
+
+    for (file, tier_id) in rename.iter() {
+        let stem = file_stem_as_string(&paths[*file])
+            .unwrap_or_else(|| format!("{file}"));
+        let suffix = format!("-{stem}");
+
+        // Tier IDs and parent references for the whole subtree
+        let mapping: Vec<(String, String)> = subtree(&eafs[*file], tier_id).into_iter()
+            .map(|id| {
+                let new_id = format!("{id}{suffix}");
+                (id, new_id)
+            })
+            .collect();
+        if let Err(err) = rename_tiers(&mut eafs[*file], &mapping) {
+            let msg = format!("(!) Failed to rename tier '{tier_id}' in '{}': {err}", paths[*file].display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+        for (old, new) in mapping.iter() {
+            println!("Renamed tier '{old}' in '{}' to '{new}'", display_name(&paths[*file]));
+        }
+    }
+
+    Ok(())
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
@@ -1,21 +1,37 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
 
 use eaf_rs::eaf::{
     Eaf,
     Scope
 };
 use regex::Regex;
+use serde::Serialize;
 
-use crate::eaf::select_tier;
+use crate::{
+    eaf::resolve_tier,
+    files::{Batch, ErrorReport},
+    output::{print_records, Format},
+    text::COMMON_PUNCTUATION,
+};
+
+/// N-gram and number of occurrences.
+#[derive(Debug, Serialize)]
+struct NgramRecord {
+    ngram: String,
+    count: usize,
+}
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
-    let eaf_path = args.get_one::<PathBuf>("eaf").unwrap(); // clap ensures val
+    let batch = Batch::from_args(args)?;
     let scope = args.get_one::<String>("scope").unwrap(); // clap default: annotation
     let size = *args.get_one::<usize>("ngram-size").unwrap();
+    let tier_selector = args.get_one::<String>("tier").map(|s| s.as_str());
+    let interactive = *args.get_one::<bool>("interactive").unwrap();
+    let format = Format::from_args(args);
     let ignore_case = *args.get_one::<bool>("ignore-case").unwrap();
     let delete_custom_string = args.get_one::<String>("remove-custom");
     let mut delete_string = match *args.get_one::<bool>("remove-common").unwrap() {
-        true => Some("[\"\'#*<>{}()\\[\\].,:;!/?=\\\\_]".to_owned()), // include '-'?
+        true => Some(COMMON_PUNCTUATION.to_owned()),
         false => None
     };
     if let Some(custom) = &delete_custom_string {
@@ -34,49 +50,99 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         None => None
     };
 
-    let eaf = match Eaf::read(eaf_path) {
-        Ok(f) => f,
-        Err(err) => {
-            let msg = format!("(!) Failed to parse '{}': {err}", eaf_path.display());
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
-        }
-    };
+    let mut errors = ErrorReport::new();
+
+    // N-grams for all files are combined into a single distribution.
+    let mut ngrams_map: HashMap<String, usize> = HashMap::new();
 
-    let ngrams_map = match scope.as_str() {
-        "annotation" => {
-            let tier = match select_tier(&eaf, false) {
-                Ok(t) => t,
-                Err(err) => {
-                    let msg = format!("(!) Failed to extract tier: {err}");
+    for eaf_path in batch.paths.iter() {
+        let eaf = match Eaf::read(eaf_path) {
+            Ok(f) => f,
+            Err(err) => {
+                let msg = format!("(!) Failed to parse '{}': {err}", eaf_path.display());
+                if !batch.is_dir() {
                     return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
                 }
-            };
-            eaf.ngram(size, delete_regex.as_ref(), Scope::Annotation(Some(tier.tier_id)))
-        },
-        "tier" => {
-            let tier = match select_tier(&eaf, false) {
-                Ok(t) => t,
-                Err(err) => {
-                    let msg = format!("(!) Failed to extract tier: {err}");
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                if format.is_structured() {
+                    eprintln!("{msg}");
                 }
-            };
-            eaf.ngram(size, delete_regex.as_ref(), Scope::Tier(Some(tier.tier_id)))
-        },
-        "file" => {
-            eaf.ngram(size, delete_regex.as_ref(), Scope::File)
-        },
-        s => {
-            let msg = format!("(!) '{s}' is not a valid scope. Choose one of 'annotation', 'tier', 'file'.");
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                errors.add(eaf_path, &err);
+                continue
+            }
+        };
+
+        if batch.is_dir() && scope != "file" && !format.is_structured() {
+            println!("[{}]", eaf_path.display());
         }
-    };
+
+        let file_ngrams = match scope.as_str() {
+            "annotation" => {
+                let tier = match resolve_tier(&eaf, tier_selector, interactive, false) {
+                    Ok(t) => t,
+                    Err(err) => {
+                        let msg = format!("(!) Failed to extract tier: {err}");
+                        if !batch.is_dir() {
+                            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                        }
+                        if format.is_structured() {
+                            eprintln!("{msg}");
+                        }
+                        errors.add(eaf_path, &err);
+                        continue
+                    }
+                };
+                eaf.ngram(size, delete_regex.as_ref(), Scope::Annotation(Some(tier.tier_id)))
+            },
+            "tier" => {
+                let tier = match resolve_tier(&eaf, tier_selector, interactive, false) {
+                    Ok(t) => t,
+                    Err(err) => {
+                        let msg = format!("(!) Failed to extract tier: {err}");
+                        if !batch.is_dir() {
+                            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                        }
+                        if format.is_structured() {
+                            eprintln!("{msg}");
+                        }
+                        errors.add(eaf_path, &err);
+                        continue
+                    }
+                };
+                eaf.ngram(size, delete_regex.as_ref(), Scope::Tier(Some(tier.tier_id)))
+            },
+            "file" => {
+                eaf.ngram(size, delete_regex.as_ref(), Scope::File)
+            },
+            s => {
+                let msg = format!("(!) '{s}' is not a valid scope. Choose one of 'annotation', 'tier', 'file'.");
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        };
+
+        for (ngram, count) in file_ngrams.iter() {
+            *ngrams_map.entry(ngram.to_owned()).or_insert(0) += count;
+        }
+    }
 
     let mut ngrams: Vec<(String, usize)> = ngrams_map.iter()
         .map(|(k,v)| (k.to_owned(), v.to_owned()))
         .collect();
         
-    ngrams.sort_by_key(|(_, v)| *v);
+    // Ties sorted on n-gram for stable output
+    ngrams.sort_by(|(n1, c1), (n2, c2)| c1.cmp(c2).then(n1.cmp(n2)));
+
+    if format.is_structured() {
+        let records: Vec<NgramRecord> = ngrams.into_iter()
+            .map(|(ngram, count)| NgramRecord {ngram, count})
+            .collect();
+        print_records(&records, format)?;
+
+        if batch.is_dir() {
+            errors.eprint(batch.len());
+        }
+
+        return Ok(())
+    }
 
     if ngrams.is_empty() {
         println!("No annotation of length {size} or greater for selected context.");
@@ -85,5 +151,9 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         println!("{:4}. {ngram:>40}: {count}", i+1)
     }
 
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
+
     Ok(())
 }
\ No newline at end of file
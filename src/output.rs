@@ -0,0 +1,65 @@
+//! Machine-readable output for commands that otherwise print tables,
+//! e.g. for consumption by other tools instead of scraping terminal output.
+//! Records are printed to stdout as a JSON array, newline delimited JSON
+//! (one object per line), or tab separated values with headers.
+
+use serde::Serialize;
+
+/// Output format, set via `--format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Format {
+    /// Human-readable tables (default).
+    #[default]
+    Table,
+    Json,
+    Ndjson,
+    Tsv,
+}
+
+impl Format {
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        match args.try_get_one::<String>("format").ok().flatten().map(|s| s.as_str()) {
+            Some("json") => Self::Json,
+            Some("ndjson") => Self::Ndjson,
+            Some("tsv") => Self::Tsv,
+            _ => Self::Table,
+        }
+    }
+
+    /// Returns `true` for structured output,
+    /// in which case only records should be printed to stdout.
+    pub fn is_structured(&self) -> bool {
+        *self != Self::Table
+    }
+}
+
+/// Prints `records` to stdout in the specified format.
+/// Prints nothing for `Format::Table`, since each command
+/// prints its own tables.
+pub fn print_records<T: Serialize>(records: &[T], format: Format) -> std::io::Result<()> {
+    match format {
+        Format::Table => (),
+        Format::Json => {
+            let json = serde_json::to_string_pretty(records)?;
+            println!("{json}");
+        },
+        Format::Ndjson => {
+            for record in records.iter() {
+                println!("{}", serde_json::to_string(record)?);
+            }
+        },
+        Format::Tsv => {
+            // Headers are derived from the field names of the first record
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(b'\t')
+                .from_writer(std::io::stdout());
+            for record in records.iter() {
+                writer.serialize(record)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            }
+            writer.flush()?;
+        },
+    }
+
+    Ok(())
+}
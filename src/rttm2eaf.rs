@@ -0,0 +1,154 @@
+//! Generate ELAN-files from a Rich Transcription Time Marked (RTTM) file,
+//! e.g. speaker diarization output. Each speaker becomes a tier, with
+//! the speaker name as tier ID and participant. If a file contains
+//! speakers on multiple channels, the channel is appended to the tier ID.
+//!
+//! RTTM-files covering multiple recordings (file IDs) are split into
+//! one ELAN-file per file ID. Media files are linked to the ELAN-file
+//! with the same file ID as the media file stem, or to all ELAN-files
+//! if no media file stem matches any file ID.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use eaf_rs::Eaf;
+use itertools::Itertools;
+use rttm_rs::{Rttm, RttmSegment};
+
+use crate::files::{append_file_name, checked_outpath, create_parent, file_stem_as_string};
+
+/// RTTM field to use as annotation value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    None,
+    /// Orthographic transcription
+    Ortho,
+    /// Speaker type, e.g. `adult_male`
+    Type,
+    Speaker,
+}
+
+impl Value {
+    fn from_str(value: &str) -> Self {
+        match value {
+            "ortho" => Self::Ortho,
+            "type" => Self::Type,
+            "speaker" => Self::Speaker,
+            _ => Self::None,
+        }
+    }
+
+    fn get(&self, seg: &RttmSegment) -> String {
+        let field = match self {
+            Self::None => None,
+            Self::Ortho => seg.orthography.to_owned(),
+            Self::Type => seg.speaker_type.to_owned(),
+            Self::Speaker => Some(seg.speaker_name.to_owned()),
+        };
+        // '<NA>' denotes a field without value in RTTM
+        field.filter(|f| f != "<NA>").unwrap_or_default()
+    }
+}
+
+/// Generates an ELAN-file from segments with the same file ID.
+fn to_eaf(segments: &[&RttmSegment], value: Value) -> std::io::Result<Eaf> {
+    let channels = segments.iter()
+        .map(|s| s.channel.to_string())
+        .unique()
+        .count();
+
+    // (annotation value, time value ms start, time value ms end, tier ID)
+    let mut annotations: Vec<(String, i64, i64, String)> = segments.iter()
+        .map(|seg| {
+            let (t1, t2) = seg.timespan_ms();
+            let tier_id = match channels {
+                1 => seg.speaker_name.to_owned(),
+                _ => format!("{}_ch{}", seg.speaker_name, seg.channel),
+            };
+            (value.get(seg), t1, t2, tier_id)
+        })
+        .collect();
+    annotations.sort_by_key(|(_, t1, t2, tier_id)| (tier_id.to_owned(), *t1, *t2));
+
+    let mut eaf = match Eaf::from_values_multi(&annotations) {
+        Ok(e) => e,
+        Err(err) => {
+            let msg = format!("(!) Failed to generate ELAN-file: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        }
+    };
+
+    // Tier ID is the speaker name, optionally with channel suffix
+    for tier in eaf.tiers.iter_mut() {
+        let speaker = segments.iter()
+            .find(|s| tier.tier_id == s.speaker_name || tier.tier_id == format!("{}_ch{}", s.speaker_name, s.channel))
+            .map(|s| s.speaker_name.to_owned());
+        tier.participant = speaker;
+    }
+
+    Ok(eaf)
+}
+
+pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
+    let rttm_path = args.get_one::<PathBuf>("rttm").unwrap(); // clap ensures value
+    let value = Value::from_str(args.get_one::<String>("value").unwrap()); // clap default 'none'
+    let media: Vec<PathBuf> = args.get_many::<PathBuf>("media")
+        .map(|m| m.cloned().collect())
+        .unwrap_or_default();
+    let out_path = args.get_one::<PathBuf>("out");
+
+    let rttm = Rttm::read(rttm_path, false)?;
+
+    // Group segments on file ID
+    let mut files: BTreeMap<String, Vec<&RttmSegment>> = BTreeMap::new();
+    for seg in rttm.iter() {
+        files.entry(seg.file_id.to_owned()).or_insert(Vec::new()).push(seg);
+    }
+
+    if files.is_empty() {
+        let msg = format!("(!) No segments in '{}'", rttm_path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
+
+    let default_path = match out_path {
+        Some(p) => create_parent(p)?,
+        None => rttm_path.with_extension("eaf")
+    };
+
+    for (file_id, segments) in files.iter() {
+        let mut eaf = to_eaf(segments, value)?;
+
+        // Link media with the file ID as file stem, otherwise all media
+        let matching: Vec<&PathBuf> = media.iter()
+            .filter(|p| file_stem_as_string(p).as_deref() == Some(file_id.as_str()))
+            .collect();
+        let link: Vec<&PathBuf> = match matching.is_empty() {
+            true => media.iter()
+                .filter(|p| !files.contains_key(&file_stem_as_string(p).unwrap_or_default()))
+                .collect(),
+            false => matching
+        };
+        link.iter()
+            .try_for_each(|p| eaf.add_media(p, None))?;
+
+        let path = match files.len() {
+            1 => default_path.to_owned(),
+            _ => append_file_name(&default_path, file_id)
+        };
+
+        println!("File ID '{file_id}':");
+        for (i, tier) in eaf.tiers.iter().enumerate() {
+            println!("  {}. {} ({} annotations)", i+1, tier.tier_id, tier.len());
+        }
+
+        match checked_outpath(&path)? {
+            Some(p) => {
+                eaf.write(&p, Some(4))?;
+                println!("Wrote {}", p.display());
+            },
+            None => println!("Skipped writing {}", path.display())
+        }
+    }
+
+    Ok(())
+}
+
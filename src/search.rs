@@ -2,6 +2,8 @@
 //! directory. Lists tier ID, annotation ID, and annotation "index"
 //! (the list number in the Grid tab in ELAN) for each match.
 //! Currently does not support regular expressions.
+//! With `--format json|ndjson|tsv` each match is printed as a record
+//! with file, tier ID, annotation index and ID, time span, and value.
 
 use std::{
     path::{Path, PathBuf},
@@ -13,8 +15,42 @@ use std::{
 use walkdir::WalkDir;
 use regex::Regex;
 use eaf_rs::eaf::Eaf;
+use serde::Serialize;
 
-use crate::{text::process_string, files::is_hidden};
+use crate::{
+    eaf::annotation_timespan,
+    files::is_hidden,
+    output::{print_records, Format},
+    text::process_string
+};
+
+/// Search match, one record per annotation.
+#[derive(Debug, Serialize)]
+struct MatchRecord {
+    file: String,
+    tier_id: String,
+    /// 1-based index, i.e. the list number in the Grid tab in ELAN.
+    index: usize,
+    annotation_id: String,
+    start_ms: Option<i64>,
+    end_ms: Option<i64>,
+    value: String,
+}
+
+impl MatchRecord {
+    fn new(eaf: &Eaf, file: &str, index: usize, tier_id: &str, annotation_id: &str, value: &str) -> Self {
+        let (start_ms, end_ms) = annotation_timespan(eaf, tier_id, annotation_id);
+        Self {
+            file: file.to_owned(),
+            tier_id: tier_id.to_owned(),
+            index,
+            annotation_id: annotation_id.to_owned(),
+            start_ms,
+            end_ms,
+            value: value.to_owned(),
+        }
+    }
+}
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     let eaf_path = args.get_one::<PathBuf>("eaf");
@@ -41,7 +77,9 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     };
     let verbose = *args.get_one::<bool>("verbose").unwrap(); // ensured by clap
     let context = *args.get_one::<bool>("context").unwrap(); // ensured by clap
+    let format = Format::from_args(args);
     let mut parse_errors: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    let mut records: Vec<MatchRecord> = Vec::new();
 
     if let Some(p) = eaf_path {
 
@@ -75,7 +113,11 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
             false => p.file_name().map(|n| n.to_string_lossy().to_string()).unwrap()
         };
 
-        if results.is_empty() {
+        if format.is_structured() {
+            for (a_idx, t_id, a_id, a_val, _a_ref_id) in results.iter() {
+                records.push(MatchRecord::new(&eaf, &path_display, *a_idx, t_id, a_id, a_val));
+            }
+        } else if results.is_empty() {
             if verbose {
                 println!("╭─[{:4}.]", path_display);
                 println!("│ No matches");
@@ -162,8 +204,15 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                         }
                     };
                     matches += results.len();
-        
-                    if results.is_empty() {
+
+                    if format.is_structured() {
+                        if !results.is_empty() {
+                            count += 1;
+                        }
+                        for (a_idx, t_id, a_id, a_val, _a_ref_id) in results.iter() {
+                            records.push(MatchRecord::new(&eaf, &path_display, *a_idx, t_id, a_id, a_val));
+                        }
+                    } else if results.is_empty() {
                         if verbose {
                             println!("╭─[{:4}. {}]", total, path_display); // file ensuread above
                             println!("│ No matches");
@@ -193,7 +242,7 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                     }
                 },
                 Err(err) => {
-                    if verbose {
+                    if verbose && !format.is_structured() {
                         println!("╭─[{:4}. {}]", count, path_display); // file ensuread above
                         println!("│ (!) Failed to parse '{}': {err}", path.display());
                         println!("╰────")
@@ -204,18 +253,28 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
 
         }
 
-        print!("Done. Found {matches} matches in {count} files. Searched {total} files.");
+        // Keep stdout free for records in structured output
+        let mut summary = format!("Done. Found {matches} matches in {count} files. Searched {total} files.");
         if parse_errors.is_empty() {
-            println!(" No errors.")
+            summary.push_str(" No errors.")
         } else {
-            println!("\n\nSome files failed to parse due to errors:");
+            summary.push_str("\n\nSome files failed to parse due to errors:");
             for (err, paths) in parse_errors.iter() {
-                println!("[ERR: {}]", err);
+                summary.push_str(&format!("\n[ERR: {}]", err));
                 for path in paths {
-                    println!("  {}", path.display())
+                    summary.push_str(&format!("\n  {}", path.display()))
                 }
             }
         }
+        match format.is_structured() {
+            true => eprintln!("{summary}"),
+            false => println!("{summary}")
+        }
+    }
+
+    if format.is_structured() {
+        print_records(&records, format)?;
+        return Ok(())
     }
 
     if regex.is_some() {
@@ -2,20 +2,40 @@
 //! By default, shifts that result in negative time values are set to zero
 //! so that ELAN's timelines always aligns with the media start time.
 
-use std::path::PathBuf;
+use std::path::Path;
 
 use eaf_rs::eaf::Eaf;
 
-use crate::files::writefile;
+use crate::files::{writefile, Batch, ErrorReport};
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
 
-    // EAF-file path
-    let path = args.get_one::<PathBuf>("eaf").unwrap(); // clap ensures value
+    // EAF-file paths
+    let batch = Batch::from_args(args)?;
 
     // Shift value, milliseconds.
     let shift = *args.get_one::<i64>("shift-value").unwrap(); // clap ensures value
 
+    let mut errors = ErrorReport::new();
+    for path in batch.paths.iter() {
+        if let Err(err) = shift_file(path, shift, &batch) {
+            if !batch.is_dir() {
+                return Err(err)
+            }
+            println!("{err}");
+            errors.add(path, &err);
+        }
+    }
+
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
+
+    Ok(())
+}
+
+/// Shifts a single ELAN-file and writes the result.
+fn shift_file(path: &Path, shift: i64, batch: &Batch) -> std::io::Result<()> {
     let mut eaf = match Eaf::read(path) {
         Ok(f) => f,
         Err(err) => {
@@ -50,7 +70,7 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                 }
             };
             file_name.push_str(&format!("_{shift}.eaf"));
-            path.with_file_name(file_name)
+            batch.outpath(&path.with_file_name(file_name))?
         },
         None => {
             let msg = format!("(!) Failed to extract file name from '{}'", path.display());
@@ -58,9 +78,13 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         }
     };
     
-    if let Err(err) = writefile(&eaf_str.as_bytes(), &eaf_path) {
-        let msg = format!("(!) Failed to write '{}': {err}", eaf_path.display());
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    match writefile(&eaf_str.as_bytes(), &eaf_path) {
+        Ok(Some(p)) => println!("Wrote {}", p.display()),
+        Ok(None) => println!("Skipped writing {}", eaf_path.display()),
+        Err(err) => {
+            let msg = format!("(!) Failed to write '{}': {err}", eaf_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
     };
 
     Ok(())
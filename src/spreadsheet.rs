@@ -0,0 +1,184 @@
+//! Read spreadsheets (`.xlsx`, `.xlsm`, `.xlsb`, `.xls`, `.ods`) as rows of cells.
+//! The first row in the sheet is used as column headers.
+//!
+//! Native time and duration cells are converted to milliseconds.
+//! Excel stores these as days, with time of day as the fractional part,
+//! whereas ODS uses ISO 8601 durations (`PT01H02M03.5S`) and date times.
+//! For date time cells only the time of day is used.
+//!
+//! Number cells are read as text, the same way as in CSV-files,
+//! so that the same column mapping applies: as time stamps,
+//! integer values are milliseconds, e.g. `13` is 13 milliseconds,
+//! and decimal values are seconds, e.g. `13.454` is 13.454 seconds.
+//! Note that a float cell with an integer value, e.g. `13.0`, is read as `13`.
+//!
+//! Other cells are read as text.
+
+use std::path::Path;
+
+use calamine::{open_workbook_auto, Data, Range, Reader};
+
+use crate::{
+    csv2eaf::hms2duration,
+    eaf2csv::ms2string,
+    files::has_extension,
+};
+
+/// Supported spreadsheet file extensions.
+pub const EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// Cell value.
+#[derive(Debug, Clone)]
+pub enum Cell {
+    Text(String),
+    /// Time or duration cell as milliseconds.
+    Ms(i64),
+}
+
+impl Cell {
+    /// Returns cell value as text, with times formatted as `HH:MM:SS.fff`.
+    pub fn text(&self) -> String {
+        match self {
+            Self::Text(t) => t.to_owned(),
+            Self::Ms(ms) => ms2string(*ms),
+        }
+    }
+
+    fn from_data(data: &Data) -> Self {
+        match data {
+            Data::Empty => Self::Text(String::new()),
+            Data::String(s) => Self::Text(s.trim().to_owned()),
+            Data::DateTime(dt) => {
+                // Durations may exceed 24 hours, for date times only time of day is used
+                let days = match dt.is_duration() {
+                    true => dt.as_f64(),
+                    false => dt.as_f64().fract()
+                };
+                Self::Ms((days * 86_400_000.).round() as i64)
+            },
+            Data::DurationIso(d) => match iso_duration2ms(d) {
+                Some(ms) => Self::Ms(ms),
+                None => Self::Text(d.to_owned())
+            },
+            Data::DateTimeIso(d) => match d.split_once('T').and_then(|(_, t)| hms2duration(t).ok()) {
+                Some(dur) => Self::Ms(dur.whole_milliseconds() as i64),
+                None => Self::Text(d.to_owned())
+            },
+            // Integer values as e.g. '13', not '13.0', to be parsed as milliseconds
+            Data::Float(f) => Self::Text(f.to_string()),
+            Data::Int(i) => Self::Text(i.to_string()),
+            // Bool, Error
+            d => Self::Text(d.to_string())
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!(self, Self::Text(t) if t.is_empty())
+    }
+}
+
+/// Returns `true` if `path` has a spreadsheet file extension.
+pub fn is_spreadsheet(path: &Path) -> bool {
+    EXTENSIONS.iter().any(|ext| has_extension(path, ext))
+}
+
+/// Converts an ISO 8601 duration, e.g. `PT01H02M03.5S`
+/// or `-PT3S`, to milliseconds.
+fn iso_duration2ms(iso: &str) -> Option<i64> {
+    let (sign, iso) = match iso.trim().strip_prefix('-') {
+        Some(d) => (-1., d),
+        None => (1., iso.trim())
+    };
+    let mut time = iso.strip_prefix('P')?;
+    let mut seconds = 0.;
+    // Days only, before the time part
+    if let Some((days, t)) = time.split_once('T') {
+        if !days.is_empty() {
+            seconds += days.strip_suffix('D')?.parse::<f64>().ok()? * 86400.;
+        }
+        time = t;
+    }
+    let mut number = String::new();
+    for c in time.chars() {
+        let unit = match c {
+            'H' => 3600.,
+            'M' => 60.,
+            'S' => 1.,
+            _ => {
+                number.push(c);
+                continue
+            }
+        };
+        seconds += number.parse::<f64>().ok()? * unit;
+        number.clear();
+    }
+    match number.is_empty() {
+        true => Some((sign * seconds * 1000.).round() as i64),
+        false => None
+    }
+}
+
+/// Reads the sheet with name `sheet`, or 1-based index if no name matches,
+/// or the first sheet if `sheet` is `None`.
+/// Returns headers from the first row, and remaining non-empty rows
+/// with their row numbers in the spreadsheet.
+pub fn read(path: &Path, sheet: Option<&str>) -> std::io::Result<(Vec<String>, Vec<(usize, Vec<Cell>)>)> {
+    let mut workbook = match open_workbook_auto(path) {
+        Ok(wb) => wb,
+        Err(err) => {
+            let msg = format!("(!) Failed to open '{}': {err}", path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    let names = workbook.sheet_names();
+    let name = match sheet {
+        None => names.first().cloned(),
+        Some(s) if names.iter().any(|n| n == s) => Some(s.to_owned()),
+        Some(s) => s.parse::<usize>().ok()
+            .and_then(|i| i.checked_sub(1))
+            .and_then(|i| names.get(i).cloned())
+    };
+    let name = match name {
+        Some(n) => n,
+        None => {
+            let msg = format!("(!) No sheet '{}' in '{}'. Available sheets: {}",
+                sheet.unwrap_or_default(),
+                path.display(),
+                names.iter().enumerate().map(|(i, n)| format!("{}. {n}", i+1)).collect::<Vec<_>>().join(", ")
+            );
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    let range: Range<Data> = match workbook.worksheet_range(&name) {
+        Ok(r) => r,
+        Err(err) => {
+            let msg = format!("(!) Failed to read sheet '{name}' in '{}': {err}", path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    // Range starts at the first non-empty cell, 0-based
+    let first_row = range.start().map(|(row, _)| row as usize).unwrap_or(0);
+
+    let mut rows = range.rows()
+        .enumerate()
+        .map(|(i, row)| (first_row + i + 1, row.iter().map(Cell::from_data).collect::<Vec<_>>()));
+
+    let headers: Vec<String> = match rows.next() {
+        Some((_, cells)) => cells.iter().map(|c| c.text()).collect(),
+        None => {
+            let msg = format!("(!) Sheet '{name}' in '{}' is empty.", path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    let rows: Vec<(usize, Vec<Cell>)> = rows
+        .filter(|(_, cells)| !cells.iter().all(|c| c.is_empty()))
+        .collect();
+
+    println!("Read sheet '{name}' ({} rows)", rows.len());
+
+    Ok((headers, rows))
+}
@@ -0,0 +1,271 @@
+//! Generates SubRip (SRT) or WebVTT (VTT) subtitles from one or more tiers.
+//!
+//! Each annotation becomes one or more cues. Annotation values are wrapped
+//! to the max line length, and annotations with more lines than fit in a cue,
+//! or longer than the max cue duration, are split into multiple cues,
+//! with time divided in proportion to the number of characters in each cue.
+//! Optionally, adjacent annotations in the same tier are merged into a single
+//! cue if the result fits within the same limits.
+//!
+//! Multiple tiers are combined into a single subtitle file, with
+//! WebVTT voice tags (`<v Speaker>`) for the tier participant or tier ID.
+//! Since SRT has no voice tags, tiers with overlapping annotations are
+//! instead written as separate tracks, one file per tier,
+//! which can also be forced for WebVTT.
+
+use std::path::PathBuf;
+
+use eaf_rs::eaf::{Eaf, Tier};
+
+use crate::{
+    eaf::find_tiers,
+    eaf2csv::ms2string,
+    files::{append_file_name, create_parent, writefile},
+    textgrid::eaf2tg::timespans,
+};
+
+use super::{Cue, SubtitleFormat};
+
+/// Limits for splitting and merging cues.
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    max_line_len: usize,
+    max_lines: usize,
+    max_duration: i64,
+    /// Max gap in milliseconds for merging adjacent annotations.
+    merge_gap: Option<i64>,
+}
+
+/// Wraps `text` into lines of at most `max_len` characters,
+/// breaking on whitespace. Words longer than `max_len` get a line of their own.
+fn wrap(text: &str, max_len: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= max_len => {
+                line.push(' ');
+                line.push_str(word);
+            },
+            _ => lines.push(word.to_owned())
+        }
+    }
+    lines
+}
+
+/// Splits a single annotation into cues according to `limits`.
+fn split(start: i64, end: i64, text: &str, voice: Option<&str>, limits: &Limits) -> Vec<Cue> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        return Vec::new()
+    }
+
+    // Number of cues required for text and duration respectively
+    let lines = wrap(text, limits.max_line_len);
+    let n_text = (lines.len() + limits.max_lines - 1) / limits.max_lines;
+    let n_duration = ((end - start + limits.max_duration - 1) / limits.max_duration).max(1) as usize;
+
+    let chunks: Vec<Vec<String>> = match n_duration > n_text {
+        // Divide words evenly, each part then fits within the line limit
+        true => {
+            let n = n_duration.min(words.len());
+            (0..n)
+                .map(|i| {
+                    let part = &words[i * words.len() / n .. (i + 1) * words.len() / n];
+                    wrap(&part.join(" "), limits.max_line_len)
+                })
+                .collect()
+        },
+        false => lines.chunks(limits.max_lines)
+            .map(|c| c.to_vec())
+            .collect()
+    };
+
+    // Time divided in proportion to number of characters
+    let total_chars: usize = chunks.iter()
+        .flat_map(|c| c.iter().map(|l| l.chars().count()))
+        .sum();
+    let mut cues: Vec<Cue> = Vec::new();
+    let mut chars = 0;
+    for chunk in chunks.into_iter() {
+        let chunk_start = start + (end - start) * chars as i64 / total_chars.max(1) as i64;
+        chars += chunk.iter().map(|l| l.chars().count()).sum::<usize>();
+        let chunk_end = start + (end - start) * chars as i64 / total_chars.max(1) as i64;
+        cues.push(Cue {
+            start: chunk_start,
+            end: chunk_end,
+            voice: voice.map(String::from),
+            lines: chunk,
+        });
+    }
+
+    cues
+}
+
+/// Merges adjacent annotation spans if the gap between them is at most
+/// `limits.merge_gap`, and the merged span fits in a single cue.
+fn merge(spans: Vec<(i64, i64, String)>, limits: &Limits) -> Vec<(i64, i64, String)> {
+    let gap = match limits.merge_gap {
+        Some(g) => g,
+        None => return spans
+    };
+    let mut merged: Vec<(i64, i64, String)> = Vec::new();
+    for (t1, t2, value) in spans.into_iter() {
+        if let Some((p1, p2, p_value)) = merged.last_mut() {
+            let text = format!("{p_value} {value}");
+            if t1 - *p2 <= gap
+                && t2 - *p1 <= limits.max_duration
+                && wrap(&text, limits.max_line_len).len() <= limits.max_lines
+            {
+                *p2 = t2;
+                *p_value = text;
+                continue
+            }
+        }
+        merged.push((t1, t2, value));
+    }
+    merged
+}
+
+/// Returns cues for `tier`, sorted on start time.
+fn tier_cues(eaf: &Eaf, tier: &Tier, voice: Option<&str>, limits: &Limits) -> Vec<Cue> {
+    let spans: Vec<(i64, i64, String)> = timespans(eaf, tier).into_iter()
+        .filter(|(_, _, value)| !value.trim().is_empty())
+        .collect();
+    merge(spans, limits).iter()
+        .flat_map(|(t1, t2, value)| split(*t1, *t2, value, voice, limits))
+        .collect()
+}
+
+/// Returns `true` if any cue in `cues1` overlaps any cue in `cues2`.
+fn overlaps(cues1: &[Cue], cues2: &[Cue]) -> bool {
+    cues1.iter()
+        .any(|c1| cues2.iter().any(|c2| c1.start < c2.end && c2.start < c1.end))
+}
+
+/// Escapes characters with special meaning in WebVTT cue text.
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Serializes `cues` as a subtitle file.
+fn to_string(cues: &[Cue], format: SubtitleFormat) -> String {
+    let mut string = String::new();
+    if format == SubtitleFormat::Vtt {
+        string.push_str("WEBVTT\n\n");
+    }
+    for (i, cue) in cues.iter().enumerate() {
+        match format {
+            SubtitleFormat::Srt => {
+                string.push_str(&format!("{}\n{} --> {}\n{}\n\n",
+                    i + 1,
+                    ms2string(cue.start).replace('.', ","),
+                    ms2string(cue.end).replace('.', ","),
+                    cue.lines.join("\n")
+                ));
+            },
+            SubtitleFormat::Vtt => {
+                let voice = cue.voice.as_deref()
+                    .map(|v| format!("<v {}>", escape_vtt(v)))
+                    .unwrap_or_default();
+                let lines: Vec<String> = cue.lines.iter().map(|l| escape_vtt(l)).collect();
+                string.push_str(&format!("{} --> {}\n{voice}{}\n\n",
+                    ms2string(cue.start),
+                    ms2string(cue.end),
+                    lines.join("\n")
+                ));
+            },
+        }
+    }
+    string
+}
+
+/// eaf2srt, eaf2vtt main
+pub fn run(args: &clap::ArgMatches, format: SubtitleFormat) -> std::io::Result<()> {
+    let eaf_path = args.get_one::<PathBuf>("eaf").unwrap(); // clap ensures value
+    let tier_selectors: Vec<&str> = args.get_many::<String>("tier")
+        .map(|v| v.map(|s| s.as_str()).collect())
+        .unwrap_or_default();
+    let limits = Limits {
+        max_line_len: *args.get_one::<usize>("max-line").unwrap(), // clap default 42
+        max_lines: *args.get_one::<usize>("max-lines").unwrap(), // clap default 2
+        max_duration: *args.get_one::<i64>("max-duration").unwrap(), // clap default 7000
+        merge_gap: args.get_one::<i64>("merge").copied(),
+    };
+    let mut tracks = *args.get_one::<bool>("tracks").unwrap();
+
+    if limits.max_line_len == 0 || limits.max_lines == 0 || limits.max_duration <= 0 {
+        let msg = format!("(!) 'max-line', 'max-lines', and 'max-duration' must be positive integers.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
+
+    let eaf = match Eaf::read(eaf_path) {
+        Ok(f) => f,
+        Err(err) => {
+            let msg = format!("(!) Failed to parse '{}': {err}", eaf_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    let tiers: Vec<Tier> = match tier_selectors.is_empty() {
+        true => eaf.tiers.iter()
+            .filter(|t| t.parent_ref.is_none())
+            .cloned()
+            .collect(),
+        false => find_tiers(&eaf, &tier_selectors)?
+    };
+
+    // Voice tags only for multiple tiers
+    let cues_per_tier: Vec<(&str, Vec<Cue>)> = tiers.iter()
+        .map(|t| {
+            let voice = match (format, tiers.len()) {
+                (SubtitleFormat::Vtt, 2..) => Some(t.participant.as_deref()
+                    .filter(|p| !p.trim().is_empty())
+                    .unwrap_or(&t.tier_id)),
+                _ => None
+            };
+            (t.tier_id.as_str(), tier_cues(&eaf, t, voice, &limits))
+        })
+        .collect();
+
+    if format == SubtitleFormat::Srt && !tracks {
+        let overlapping = cues_per_tier.iter().enumerate()
+            .any(|(i, (_, c1))| cues_per_tier.iter().skip(i + 1).any(|(_, c2)| overlaps(c1, c2)));
+        if overlapping {
+            println!("Tiers overlap. Writing one SRT-file per tier.");
+            tracks = true;
+        }
+    }
+
+    let path = match args.get_one::<PathBuf>("out") {
+        Some(p) => create_parent(p)?,
+        None => eaf_path.with_extension(format.extension())
+    };
+
+    let outputs: Vec<(PathBuf, Vec<Cue>)> = match tracks && cues_per_tier.len() > 1 {
+        true => cues_per_tier.into_iter()
+            .map(|(id, cues)| (append_file_name(&path, id), cues))
+            .collect(),
+        false => {
+            let mut cues: Vec<Cue> = cues_per_tier.into_iter()
+                .flat_map(|(_, c)| c)
+                .collect();
+            cues.sort_by_key(|c| (c.start, c.end));
+            vec![(path, cues)]
+        }
+    };
+
+    for (sub_path, cues) in outputs.iter() {
+        match writefile(to_string(cues, format).as_bytes(), sub_path) {
+            Ok(Some(p)) => println!("Wrote {} ({} cues)", p.display(), cues.len()),
+            Ok(None) => println!("Skipped writing {}", sub_path.display()),
+            Err(err) => {
+                let msg = format!("(!) Failed to write '{}': {err}", sub_path.display());
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+//! Subtitles in SubRip (SRT) and WebVTT (VTT) format.
+
+pub mod eaf2sub;
+pub mod sub2eaf;
+
+/// Subtitle format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    /// SubRip
+    Srt,
+    /// WebVTT
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+        }
+    }
+}
+
+/// Subtitle cue.
+#[derive(Debug, Clone, Default)]
+pub struct Cue {
+    /// Start time in milliseconds.
+    pub start: i64,
+    /// End time in milliseconds.
+    pub end: i64,
+    /// Speaker, for WebVTT voice tags.
+    pub voice: Option<String>,
+    pub lines: Vec<String>,
+}
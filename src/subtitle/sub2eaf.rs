@@ -0,0 +1,263 @@
+//! Generates an ELAN-file from SubRip (SRT) or WebVTT (VTT) subtitles.
+//! Each cue becomes an annotation in a time-aligned main tier,
+//! with multi-line cue text joined into a single line.
+//!
+//! - Cue identifiers and settings (e.g. `align:start`) are ignored.
+//! - Styling tags (e.g. `<i>`, `<c.yellow>`, `{\an8}`) are removed,
+//!   and HTML entities (e.g. `&amp;`) are unescaped.
+//! - WebVTT `NOTE`, `STYLE`, and `REGION` blocks are ignored.
+//! - WebVTT voice spans (`<v Speaker>`) can be routed to one tier per voice,
+//!   with participant set to the voice name. Otherwise the voice name is discarded,
+//!   and all text in the cue becomes a single annotation.
+//! - Cues overlapping an earlier cue in the same tier are dropped with a warning,
+//!   since annotations in a time-aligned tier can not overlap.
+//!
+//! Timestamps are parsed with `csv2eaf::hms2duration()`, so both `,` and `.`
+//! are accepted as millisecond separators. WebVTT timestamps without hours
+//! (`MM:SS.fff`) are also accepted.
+
+use std::path::{Path, PathBuf};
+
+use eaf_rs::eaf::Eaf;
+use regex::Regex;
+
+use crate::{
+    csv2eaf::hms2duration,
+    eaf2csv::ms2string,
+    files::{create_parent, writefile},
+};
+
+use super::SubtitleFormat;
+
+/// Cue text for a single voice.
+#[derive(Debug, Clone)]
+struct VoiceText {
+    voice: Option<String>,
+    text: String,
+}
+
+/// Parses a subtitle timestamp to milliseconds.
+fn timestamp2ms(timestamp: &str) -> Option<i64> {
+    let timestamp = timestamp.trim();
+    // WebVTT hours are optional
+    let hms = match timestamp.split(':').count() {
+        2 => format!("00:{timestamp}"),
+        3 => timestamp.to_owned(),
+        _ => return None
+    };
+    hms2duration(&hms).ok()
+        .map(|d| d.whole_milliseconds() as i64)
+}
+
+/// Parses the timing line `<start> --> <end> [settings]`.
+fn parse_timing(line: &str) -> Option<(i64, i64)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+    Some((timestamp2ms(start)?, timestamp2ms(end)?))
+}
+
+/// Unescapes HTML entities used in subtitles.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&lrm;", "")
+        .replace("&rlm;", "")
+        .replace("&amp;", "&")
+}
+
+/// Splits cue text into parts per voice, with styling tags removed.
+/// Text before the first voice span has no voice.
+/// All text has no voice if `voice_rx` is `None`.
+fn voice_texts(text: &str, voice_rx: Option<&Regex>, tag_rx: &Regex) -> Vec<VoiceText> {
+    let mut parts: Vec<VoiceText> = Vec::new();
+    let mut voice: Option<String> = None;
+    let mut cursor = 0;
+
+    let push = |voice: &Option<String>, raw: &str, parts: &mut Vec<VoiceText>| {
+        let clean = unescape(&tag_rx.replace_all(raw, ""));
+        let clean = clean.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !clean.is_empty() {
+            parts.push(VoiceText { voice: voice.to_owned(), text: clean });
+        }
+    };
+
+    for caps in voice_rx.iter().flat_map(|rx| rx.captures_iter(text)) {
+        let m = caps.get(0).unwrap(); // group 0 is always set
+        push(&voice, &text[cursor..m.start()], &mut parts);
+        voice = caps.get(1)
+            .map(|v| v.as_str().trim().to_owned())
+            .filter(|v| !v.is_empty());
+        cursor = m.end();
+    }
+    push(&voice, &text[cursor..], &mut parts);
+
+    parts
+}
+
+/// Parses subtitles into `(start ms, end ms, text per voice)`.
+fn parse(content: &str, format: SubtitleFormat) -> Vec<(i64, i64, Vec<VoiceText>)> {
+    // Voice span start tag, with optional classes, e.g. '<v.loud Speaker>'
+    let voice_rx = Regex::new(r"<v(?:\.[^\s>]*)?(?:\s+([^>]*))?>").unwrap();
+    // Remaining tags, incl. closing voice tags, and SRT/ASS style overrides
+    let tag_rx = Regex::new(r"<[^>]*>|\{\\[^}]*\}").unwrap();
+
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+
+    let mut cues: Vec<(i64, i64, Vec<VoiceText>)> = Vec::new();
+    for block in content.split("\n\n") {
+        let lines: Vec<&str> = block.lines()
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+        if lines.is_empty() {
+            continue
+        }
+        if format == SubtitleFormat::Vtt
+            && ["WEBVTT", "NOTE", "STYLE", "REGION"].iter().any(|kw| lines[0].starts_with(kw))
+        {
+            continue
+        }
+        // Identifier lines preceding the timing line are ignored
+        let timing_idx = match lines.iter().position(|l| l.contains("-->")) {
+            Some(i) => i,
+            None => continue
+        };
+        // End must be after start
+        let (start, end) = match parse_timing(lines[timing_idx]) {
+            Some((start, end)) if end > start => (start, end),
+            _ => {
+                println!("(!) Skipping cue with invalid timing '{}'", lines[timing_idx]);
+                continue
+            }
+        };
+        let text = lines[timing_idx + 1 ..].join(" ");
+        let voice_rx = match format {
+            SubtitleFormat::Vtt => Some(&voice_rx),
+            SubtitleFormat::Srt => None,
+        };
+        let parts = voice_texts(&text, voice_rx, &tag_rx);
+        if !parts.is_empty() {
+            cues.push((start, end, parts));
+        }
+    }
+
+    cues
+}
+
+/// Returns the subtitle format for `path`, based on file extension
+/// or the WebVTT header.
+fn detect_format(path: &Path, content: &str) -> SubtitleFormat {
+    let is_vtt = path.extension().map(|e| e.eq_ignore_ascii_case("vtt")).unwrap_or(false)
+        || content.trim_start_matches('\u{feff}').starts_with("WEBVTT");
+    match is_vtt {
+        true => SubtitleFormat::Vtt,
+        false => SubtitleFormat::Srt,
+    }
+}
+
+/// sub2eaf main
+pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
+    let sub_path = args.get_one::<PathBuf>("subtitles").unwrap(); // clap ensures value
+    let tier_id = args.get_one::<String>("tier").unwrap(); // clap default 'subtitles'
+    let split_voices = *args.get_one::<bool>("split-voices").unwrap();
+
+    // Media files to link.
+    let media: Vec<PathBuf> = args.get_many::<PathBuf>("media")
+        .map(|m| m.into_iter().map(|p| p.into()).collect())
+        .unwrap_or_default();
+
+    let content = std::fs::read_to_string(sub_path)?;
+    let format = detect_format(sub_path, &content);
+    let cues = parse(&content, format);
+
+    if cues.is_empty() {
+        let msg = format!("(!) No cues in '{}'", sub_path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
+
+    // (annotation value, time value ms start, time value ms end, tier ID)
+    let mut annotations: Vec<(String, i64, i64, String)> = Vec::new();
+    for (start, end, parts) in cues.iter() {
+        // Parts with the same target tier are joined into a single annotation
+        let mut values: Vec<(String, Vec<&str>)> = Vec::new();
+        for part in parts.iter() {
+            let id = match (split_voices, &part.voice) {
+                (true, Some(v)) => v.to_owned(),
+                _ => tier_id.to_owned()
+            };
+            match values.iter_mut().find(|(i, _)| i == &id) {
+                Some((_, texts)) => texts.push(&part.text),
+                None => values.push((id, vec![part.text.as_str()]))
+            }
+        }
+        for (id, texts) in values.into_iter() {
+            annotations.push((texts.join(" "), *start, *end, id));
+        }
+    }
+    annotations.sort_by(|a, b| a.3.cmp(&b.3).then(a.1.cmp(&b.1)));
+
+    // Drop annotations overlapping an earlier annotation in the same tier
+    let mut dropped: Vec<(String, i64, i64, String)> = Vec::new();
+    let mut kept: Vec<(String, i64, i64, String)> = Vec::new();
+    for annotation in annotations.into_iter() {
+        match kept.last() {
+            Some(prev) if prev.3 == annotation.3 && annotation.1 < prev.2 => dropped.push(annotation),
+            _ => kept.push(annotation)
+        }
+    }
+    let annotations = kept;
+    if !dropped.is_empty() {
+        println!("(!) Dropped {} cues overlapping an earlier cue in the same tier:", dropped.len());
+        for (value, start, end, id) in dropped.iter() {
+            println!("  {id}: {} --> {} {value}", ms2string(*start), ms2string(*end));
+        }
+    }
+
+    let mut eaf = match Eaf::from_values_multi(&annotations) {
+        Ok(e) => e,
+        Err(err) => {
+            let msg = format!("(!) Failed to generate ELAN-file: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        }
+    };
+
+    // Voice tiers have the voice as participant
+    if split_voices {
+        for tier in eaf.tiers.iter_mut() {
+            if cues.iter().flat_map(|(_, _, p)| p.iter()).any(|p| p.voice.as_deref() == Some(tier.tier_id.as_str())) {
+                tier.participant = Some(tier.tier_id.to_owned());
+            }
+        }
+    }
+
+    // Link media files
+    eaf.with_media_mut(&media);
+
+    println!("Generated the following tiers:");
+    for (i, tier) in eaf.tiers.iter().enumerate() {
+        println!("{}. {} ({} annotations)", i+1, tier.tier_id, tier.len());
+    }
+
+    let eaf_path = match args.get_one::<PathBuf>("out") {
+        Some(p) => create_parent(p)?,
+        None => sub_path.with_extension("eaf")
+    };
+    let eaf_string = match eaf.to_string(Some(4)) {
+        Ok(s) => s,
+        Err(err) => {
+            let msg = format!("(!) Failed to generate EAF: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    match writefile(eaf_string.as_bytes(), &eaf_path) {
+        Ok(Some(p)) => println!("Wrote {}", p.display()),
+        Ok(None) => println!("Skipped writing {}", eaf_path.display()),
+        Err(err) => {
+            let msg = format!("(!) Failed to write '{}': {err}", eaf_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    }
+
+    Ok(())
+}
@@ -2,6 +2,10 @@ use std::collections::HashMap;
 
 use regex::Regex;
 
+/// Regular expression character class for common punctuation and brackets,
+/// e.g. for removal via `process_string()`.
+pub const COMMON_PUNCTUATION: &str = "[\"\'#*<>{}()\\[\\].,:;!/?=\\\\_]"; // include '-'?
+
 /// Replaces non-ASCII and whitespace with specified `char` for each,
 /// truncates to specified `len`, and removes anything captured by `regex`. Trims leading and trailing whitespace,
 /// after truncation, meaning the string may be shorter than the specified `len`.
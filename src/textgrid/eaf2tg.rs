@@ -0,0 +1,134 @@
+//! Converts an ELAN-file to a Praat TextGrid.
+//! Each tier becomes an IntervalTier. Referred tiers without time slots
+//! of their own inherit the time span of the corresponding annotation
+//! in the parent tier. Gaps between annotations are filled with empty
+//! intervals, since Praat requires each IntervalTier to cover the full
+//! time span of the TextGrid.
+
+use std::path::PathBuf;
+
+use eaf_rs::eaf::{Eaf, Tier};
+use textgrid_rs::{Interval, IntervalTier, TextGrid, TextGridTier};
+
+use crate::files::{checked_outpath, create_parent};
+
+/// Returns annotations in `tier` as `(start_ms, end_ms, value)`,
+/// sorted on start time. Annotations with no time values set
+/// use the time span of the main annotation they refer to.
+/// Annotations that share the same time span (e.g. tokenized tiers)
+/// are joined into a single, whitespace delimited value.
+pub fn timespans(eaf: &Eaf, tier: &Tier) -> Vec<(i64, i64, String)> {
+    let mut spans: Vec<(i64, i64, String)> = tier.annotations.iter()
+        .filter_map(|a| {
+            let (ts1, ts2) = match a.ts_val() {
+                (Some(t1), Some(t2)) => (t1, t2),
+                _ => match eaf.main_annotation(&a.id()).map(|m| m.ts_val()) {
+                    Some((Some(t1), Some(t2))) => (t1, t2),
+                    _ => return None
+                }
+            };
+            Some((ts1, ts2, a.to_str().to_owned()))
+        })
+        .collect();
+
+    spans.sort_by_key(|(t1, t2, _)| (*t1, *t2));
+
+    let mut joined: Vec<(i64, i64, String)> = Vec::new();
+    for (t1, t2, value) in spans.into_iter() {
+        match joined.last_mut() {
+            Some((p1, p2, p_value)) if *p1 == t1 && *p2 == t2 => {
+                p_value.push(' ');
+                p_value.push_str(&value);
+            },
+            _ => joined.push((t1, t2, value))
+        }
+    }
+
+    joined
+}
+
+/// Generates an IntervalTier spanning `0` to `end_ms`.
+/// Gaps are filled with empty intervals. Overlapping annotations
+/// are truncated to start where the previous one ends,
+/// since Praat does not accept overlapping intervals.
+fn interval_tier(name: &str, spans: &[(i64, i64, String)], end_ms: i64) -> IntervalTier {
+    let mut intervals: Vec<Interval> = Vec::new();
+    let mut cursor: i64 = 0;
+
+    for (t1, t2, value) in spans.iter() {
+        let start = (*t1).max(cursor);
+        if *t2 <= start {
+            continue
+        }
+        if start > cursor {
+            intervals.push(Interval::new(cursor as f64 / 1000., start as f64 / 1000., ""));
+        }
+        intervals.push(Interval::new(start as f64 / 1000., *t2 as f64 / 1000., value));
+        cursor = *t2;
+    }
+
+    if cursor < end_ms || intervals.is_empty() {
+        intervals.push(Interval::new(cursor as f64 / 1000., end_ms as f64 / 1000., ""));
+    }
+
+    IntervalTier::new(name, 0., end_ms as f64 / 1000., &intervals)
+}
+
+/// Converts `eaf` to a TextGrid with one IntervalTier per tier.
+pub fn eaf2textgrid(eaf: &Eaf) -> TextGrid {
+    let tier_spans: Vec<(&str, Vec<(i64, i64, String)>)> = eaf.tiers.iter()
+        .map(|t| (t.tier_id.as_str(), timespans(eaf, t)))
+        .collect();
+
+    // TextGrid end is the end of the last annotation across all tiers
+    let end_ms = tier_spans.iter()
+        .flat_map(|(_, spans)| spans.iter().map(|(_, t2, _)| *t2))
+        .max()
+        .unwrap_or(0);
+
+    let tiers: Vec<TextGridTier> = tier_spans.iter()
+        .map(|(id, spans)| TextGridTier::IntervalTier(interval_tier(id, spans, end_ms)))
+        .collect();
+
+    TextGrid::new(0., end_ms as f64 / 1000., &tiers)
+}
+
+/// eaf2tg main
+pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
+    let eaf_path = args.get_one::<PathBuf>("eaf").unwrap(); // clap ensures value
+
+    let eaf = match Eaf::read(eaf_path) {
+        Ok(f) => f,
+        Err(err) => {
+            let msg = format!("(!) Failed to parse '{}': {err}", eaf_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    let textgrid = eaf2textgrid(&eaf);
+
+    let tg_path = match args.get_one::<PathBuf>("out") {
+        Some(p) => create_parent(p)?,
+        None => eaf_path.with_extension("TextGrid")
+    };
+    let tg_path = match checked_outpath(&tg_path)? {
+        Some(p) => p,
+        None => {
+            println!("Skipped writing {}", tg_path.display());
+            return Ok(())
+        }
+    };
+
+    if let Err(err) = textgrid.write(&tg_path) {
+        let msg = format!("(!) Failed to write '{}': {err}", tg_path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
+
+    println!("Converted the following tiers:");
+    for (i, tier) in eaf.tiers.iter().enumerate() {
+        println!("{}. {} ({} annotations)", i+1, tier.tier_id, tier.len());
+    }
+    println!("Wrote {}", tg_path.display());
+
+    Ok(())
+}
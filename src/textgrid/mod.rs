@@ -0,0 +1,2 @@
+pub mod eaf2tg;
+pub mod tg2eaf;
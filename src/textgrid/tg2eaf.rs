@@ -0,0 +1,140 @@
+//! Generates an ELAN-file from a Praat TextGrid.
+//! IntervalTiers become time-aligned main tiers, with empty intervals discarded.
+//! PointTiers become time-aligned main tiers with fixed-width annotations,
+//! starting at the point. The width can be set via `--point-width`, but
+//! annotations always end at or before the next point. Points less than 1 ms
+//! after the previous point are skipped with a warning.
+//! Interval boundaries are rounded to the nearest millisecond,
+//! so that shared boundaries between adjacent intervals map to the same value.
+
+use std::path::PathBuf;
+
+use eaf_rs::eaf::Eaf;
+use textgrid_rs::{TextGrid, TextGridTier};
+
+use crate::files::{create_parent, writefile};
+
+/// Converts seconds to milliseconds, rounded to the nearest integer.
+fn sec2ms(seconds: f64) -> i64 {
+    (seconds * 1000.).round() as i64
+}
+
+/// Returns annotations in the form
+/// `(annotation value, time value ms start, time value ms end, tier ID)`,
+/// as expected by `Eaf::from_values_multi()`.
+pub fn textgrid2values(textgrid: &TextGrid, point_width: i64) -> Vec<(String, i64, i64, String)> {
+    let mut annotations: Vec<(String, i64, i64, String)> = Vec::new();
+
+    for tier in textgrid.tiers.iter() {
+        match tier {
+            TextGridTier::IntervalTier(t) => {
+                t.intervals.iter()
+                    .filter(|i| !i.text.trim().is_empty())
+                    .for_each(|i| {
+                        annotations.push((
+                            i.text.trim().to_owned(),
+                            sec2ms(i.xmin),
+                            sec2ms(i.xmax),
+                            t.name.to_owned()
+                        ))
+                    });
+            },
+            TextGridTier::PointTier(t) => {
+                let mut points: Vec<(i64, &str)> = t.points.iter()
+                    .map(|p| (sec2ms(p.time), p.mark.trim()))
+                    .collect();
+                points.sort_by_key(|(start, _)| *start);
+                for (i, (start, mark)) in points.iter().enumerate() {
+                    // Points less than 1 ms after the previous point can not be annotated
+                    if i > 0 && points[i - 1].0 == *start {
+                        println!("(!) Skipping point '{mark}' at {start} ms in '{}': Less than 1 ms after the previous point.", t.name);
+                        continue
+                    }
+                    // Ends before the next point, so that annotations do not overlap
+                    let end = match points[i + 1 ..].iter().find(|(next, _)| next > start) {
+                        Some((next, _)) => (start + point_width).min(*next),
+                        None => start + point_width
+                    };
+                    annotations.push((
+                        mark.to_string(),
+                        *start,
+                        end,
+                        t.name.to_owned()
+                    ))
+                }
+            },
+        }
+    }
+
+    annotations
+}
+
+/// tg2eaf main
+pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
+    let tg_path = args.get_one::<PathBuf>("textgrid").unwrap(); // clap ensures value
+    let point_width = *args.get_one::<i64>("point-width").unwrap(); // clap default 50
+
+    // Media files to link.
+    let media: Vec<PathBuf> = args.get_many::<PathBuf>("media")
+        .map(|m| m.into_iter().map(|p| p.into()).collect())
+        .unwrap_or_default();
+
+    if point_width <= 0 {
+        let msg = format!("(!) 'point-width' must be a positive integer.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
+
+    let textgrid = match TextGrid::from_path(tg_path) {
+        Ok(tg) => tg,
+        Err(err) => {
+            let msg = format!("(!) Failed to parse '{}': {err}", tg_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        }
+    };
+
+    let annotations = textgrid2values(&textgrid, point_width);
+
+    if annotations.is_empty() {
+        let msg = format!("(!) No non-empty intervals or points in '{}'", tg_path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+    }
+
+    let mut eaf = match Eaf::from_values_multi(&annotations) {
+        Ok(e) => e,
+        Err(err) => {
+            let msg = format!("(!) Failed to generate ELAN-file: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        }
+    };
+
+    // Link media files
+    eaf.with_media_mut(&media);
+
+    println!("Generated the following tiers:");
+    for (i, tier) in eaf.tiers.iter().enumerate() {
+        println!("{}. {} ({} annotations)", i+1, tier.tier_id, tier.len());
+    }
+
+    let eaf_path = match args.get_one::<PathBuf>("out") {
+        Some(p) => create_parent(p)?,
+        None => tg_path.with_extension("eaf")
+    };
+    let eaf_string = match eaf.to_string(Some(4)) {
+        Ok(s) => s,
+        Err(err) => {
+            let msg = format!("(!) Failed to generate EAF: {err}");
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        }
+    };
+
+    match writefile(eaf_string.as_bytes(), &eaf_path) {
+        Ok(Some(p)) => println!("Wrote {}", p.display()),
+        Ok(None) => println!("Skipped writing {}", eaf_path.display()),
+        Err(err) => {
+            let msg = format!("(!) Failed to write '{}': {err}", eaf_path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        },
+    }
+
+    Ok(())
+}
@@ -2,19 +2,35 @@
 //! such as brackets, punctuation etc with the `strip` flag. If these remove too much, or are
 //! not sufficient, user the `prefix` and/or `suffix` options to customize.
 //! Possible to list words/tokens in a specific tier, including word distribution/frequency.
+//! If a directory is specified, words/tokens for all ELAN-files are combined.
 //! 
 //! The `strip` flag removes the following:
 //! - prefixes: `#*_<{([-"'=`
 //! - suffixes: `#*_>})]-"'=.,:;!?`
+//!
+//! With `--format json|ndjson|tsv` tokens are printed as records with
+//! a count for each token, regardless of the `distribution` flag.
 
-use std::{path::{Path, PathBuf}, collections::HashMap};
+use std::collections::HashMap;
+
+use serde::Serialize;
 
 use super::eaf;
+use crate::{
+    files::{Batch, ErrorReport},
+    output::{print_records, Format}
+};
 
 use eaf_rs::eaf::Eaf;
 
+/// Token and number of occurrences.
+#[derive(Debug, Serialize)]
+struct TokenRecord {
+    token: String,
+    count: usize,
+}
+
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
-    let path = args.get_one::<PathBuf>("eaf");
     let ignore_case = *args.get_one::<bool>("ignore-case").unwrap(); // ensured by clap
     let mut prefix = args.get_one::<String>("prefix").cloned();
     let mut suffix = args.get_one::<String>("suffix").cloned();
@@ -29,53 +45,101 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
             .or(Some(common_suf.to_owned()));
     };
     let distribution = *args.get_one::<bool>("distribution").unwrap();
-    let select_tier = *args.get_one::<bool>("select-tier").unwrap();
+    // '--tier' without a value prompts for a tier
+    let tier_selector = args.get_one::<String>("select-tier").map(|s| s.as_str());
+    let interactive = *args.get_one::<bool>("interactive").unwrap();
     let alphaorder = *args.get_one::<bool>("sort-alphabetically").unwrap();
     let reverse = *args.get_one::<bool>("sort-reverse").unwrap();
+    let format = Format::from_args(args);
     // distribution = count instances of each words so set unique to false
     let unique = match distribution {
         true => false,
         false => *args.get_one::<bool>("unique").unwrap()
     };
 
-    let mut tokens: Vec<String> = Vec::new();
+    let batch = Batch::from_args(args)?;
+    let mut errors = ErrorReport::new();
 
-    if let Some(p) = path {
-        if Path::new(p).is_dir() {
-            let msg = format!("(!) {} is a directory.", p.display());
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
-        }
+    // Tokens for all files are combined into a single list/distribution.
+    let mut tokens: Vec<String> = Vec::new();
 
+    for p in batch.paths.iter() {
         let eaf = match Eaf::read(p) {
             Ok(f) => f,
             Err(err) => {
                 let msg = format!("(!) Failed to parse '{}': {err}", p.display());
-                return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                if !batch.is_dir() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+                if format.is_structured() {
+                    eprintln!("{msg}");
+                }
+                errors.add(p, &err);
+                continue
             }
         };
 
-        if select_tier {
-            let tier = match eaf::select_tier(&eaf, false) {
+        if tier_selector.is_some() {
+            if batch.is_dir() && !format.is_structured() {
+                println!("[{}]", p.display());
+            }
+            let tier = match eaf::resolve_tier(&eaf, tier_selector, interactive, false) {
                 Ok(t) => t,
                 Err(err) => {
                     let msg = format!("(!) Failed to extract tier: {err}");
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                    if !batch.is_dir() {
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                    }
+                    if format.is_structured() {
+                        eprintln!("{msg}");
+                    }
+                    errors.add(p, &err);
+                    continue
                 }
             };
-            tokens = tier.tokens(prefix.as_deref(), suffix.as_deref(), unique, ignore_case);
+            tokens.extend(tier.tokens(prefix.as_deref(), suffix.as_deref(), unique, ignore_case));
         } else {
-            tokens = eaf.tokens(prefix.as_deref(), suffix.as_deref(), unique, ignore_case);
+            tokens.extend(eaf.tokens(prefix.as_deref(), suffix.as_deref(), unique, ignore_case));
         }
     }
 
-    // if let Some(d) = dir {
-    //     if Path::new(d).is_file() {
-    //         println!("(!) {d} is a file.");
-    //         std::process::exit(1)
-    //     }
+    // Unique tokens must be re-evaluated across files
+    if unique && batch.len() > 1 {
+        tokens.sort();
+        tokens.dedup();
+    }
+
+    if format.is_structured() {
+        let mut count: HashMap<String, usize> = HashMap::new();
+        tokens.iter()
+            .for_each(|w| {
+                *count.entry(w.to_owned()).or_insert(0) += 1;
+            });
+
+        let mut records: Vec<TokenRecord> = count.into_iter()
+            .map(|(token, count)| TokenRecord {token, count})
+            .collect();
+
+        // Same order as the printed distribution,
+        // ties sorted alphabetically for stable output
+        if alphaorder {
+            records.sort_by(|a, b| a.token.cmp(&b.token));
+        } else {
+            records.sort_by(|a, b| a.count.cmp(&b.count).then(a.token.cmp(&b.token)));
+        }
+
+        if reverse {
+            records.reverse()
+        }
+
+        print_records(&records, format)?;
+
+        if batch.is_dir() {
+            errors.eprint(batch.len());
+        }
 
-    //     unimplemented!()
-    // }
+        return Ok(())
+    }
 
     if distribution {
         let mut count: HashMap<String, usize> = HashMap::new();
@@ -114,5 +178,9 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     println!("Strip prefixes: {}", prefix.as_deref().unwrap_or("None"));
     println!("Strip suffixes: {}", suffix.as_deref().unwrap_or("None"));
 
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
+
     Ok(())
 }
\ No newline at end of file
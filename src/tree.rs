@@ -1,12 +1,11 @@
 //! NOT YET IMPLEMENTED
 
-use std::{
-    path::PathBuf,
-    collections::HashMap
-};
+use std::collections::HashMap;
 
 use eaf_rs::{eaf::Eaf, Tier};
 
+use crate::files::{Batch, ErrorReport};
+
 #[derive(Debug, Default)]
 /// HashMap<TIER_ID, CHILD_TIER_IDs>
 // struct TierTree(HashMap<TierType, Vec<TierType>>);
@@ -172,17 +171,33 @@ impl TierTree {
 }
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
-    let path: &PathBuf = args.get_one("eaf").unwrap();
-    let eaf = match Eaf::read(path) {
-        Ok(f) => f,
-        Err(err) => {
-            println!("(!) Failed to parse '{}': {err}", path.display());
-            std::process::exit(1)
+    let batch = Batch::from_args(args)?;
+
+    let mut errors = ErrorReport::new();
+    for path in batch.paths.iter() {
+        let eaf = match Eaf::read(path) {
+            Ok(f) => f,
+            Err(err) => {
+                if !batch.is_dir() {
+                    let msg = format!("(!) Failed to parse '{}': {err}", path.display());
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                }
+                errors.add(path, &err);
+                continue
+            }
+        };
+
+        if batch.is_dir() {
+            println!("[{}]", path.display());
         }
-    };
 
-    let tree = TierTree::new(&eaf);
-    tree.print(None, 0);
+        let tree = TierTree::new(&eaf);
+        tree.print(None, 0);
+    }
+
+    if batch.is_dir() {
+        errors.print(batch.len());
+    }
 
     // TierTree::print(&eaf);
 
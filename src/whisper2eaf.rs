@@ -9,7 +9,7 @@ use std::path::PathBuf;
 
 use eaf_rs::Eaf;
 
-use crate::{clips::Clips, files::has_extension, whisper::{WhisperJson, WhisperTsJson}};
+use crate::{clips::Clips, files::{checked_outpath, create_parent, has_extension}, whisper::{WhisperJson, WhisperTsJson}};
 
 pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
     let json_path = args.get_one::<PathBuf>("json"); // required unless "dir"
@@ -22,6 +22,12 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
         .unwrap_or_default();
 
     let no_speech_threshold = args.get_one::<f64>("no-speech").unwrap();
+    let out_path = args.get_one::<PathBuf>("out");
+
+    if out_path.is_some() && json_dir.is_some() && !join_json {
+        let msg = format!("(!) 'out' requires a single JSON-file, or 'dir' together with 'join'.");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+    }
 
     let json_paths = if let Some(dir) = json_dir {
         dir.read_dir()?
@@ -69,17 +75,25 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                     .to_string();
                 eaf.prefix_tier_all_mut(&prefix)?;
             }
-            let path = json_dir
-                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "No value set for 'dir'"))?
-                .canonicalize()?
-                .with_extension("eaf");
+            let path = match out_path {
+                Some(o) => create_parent(o)?,
+                None => json_dir
+                    .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "No value set for 'dir'"))?
+                    .canonicalize()?
+                    .with_extension("eaf")
+            };
 
             // Add optional media files
             media_paths.iter()
                 .try_for_each(|p| eaf.add_media(p, None))?;
 
-            eaf.write(&path, Some(4))?;
-            println!("Wrote {}", path.display());
+            match checked_outpath(&path)? {
+                Some(p) => {
+                    eaf.write(&p, Some(4))?;
+                    println!("Wrote {}", p.display());
+                },
+                None => println!("Skipped writing {}", path.display())
+            }
         } else {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "No 'clips' file provided"))
         }
@@ -93,14 +107,22 @@ pub fn run(args: &clap::ArgMatches) -> std::io::Result<()> {
                 },
             };
 
-            let p = path.with_extension("eaf");
+            let p = match out_path {
+                Some(o) => create_parent(o)?,
+                None => path.with_extension("eaf")
+            };
 
             // Add optional media files
             media_paths.iter()
                 .try_for_each(|p| eaf.add_media(p, None))?;
 
-            eaf.write(&p, Some(4))?;
-            println!("Wrote {}", p.display());
+            match checked_outpath(&p)? {
+                Some(p) => {
+                    eaf.write(&p, Some(4))?;
+                    println!("Wrote {}", p.display());
+                },
+                None => println!("Skipped writing {}", p.display())
+            }
         }
     }
 